@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::git;
+use crate::modifyfile;
+
+// Findings that already existed when the baseline was written, as
+// (file, identifier, fingerprint). The fingerprint covers the identifier and
+// the text of its declaration line, not the line number, so code moving up
+// or down the file doesn't resurrect old findings.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    entries: HashSet<(String, String, String)>,
+}
+
+impl Baseline {
+    pub fn contains(&self, file_key: &str, identifier: &str, fingerprint: &str) -> bool {
+        self.entries
+            .contains(&(file_key.to_string(), identifier.to_string(), fingerprint.to_string()))
+    }
+
+    pub fn load(baseline_path: &str) -> io::Result<Self> {
+        let content = modifyfile::read_file(baseline_path)?;
+        let mut entries = HashSet::new();
+        for (line_no, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 3 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: expected file, identifier and fingerprint separated by tabs", baseline_path, line_no + 1),
+                ));
+            }
+            entries.insert((fields[0].to_string(), fields[1].to_string(), fields[2].to_string()));
+        }
+        Ok(Baseline { entries })
+    }
+
+    pub fn save(entries: &[(String, String, String)], baseline_path: &str) -> io::Result<()> {
+        let mut file = File::create(baseline_path)?;
+        writeln!(file, "# bad_variable_changer baseline")?;
+        writeln!(file, "# file\tidentifier\tfingerprint")?;
+        for (file_key, identifier, fingerprint) in entries {
+            writeln!(file, "{}\t{}\t{}", file_key, identifier, fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+// The same file named as ./src/a.py or src/a.py gets the same key
+pub fn file_key(file_path: &Path) -> String {
+    let path = git::canonical(file_path);
+    let cwd = std::env::current_dir().map(|dir| git::canonical(&dir)).unwrap_or_default();
+    path.strip_prefix(&cwd).unwrap_or(&path).display().to_string()
+}
+
+pub fn fingerprint(content: &str, offset: usize, identifier: &str) -> String {
+    let start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[offset..].find('\n').map_or(content.len(), |i| offset + i);
+    // Indentation and spacing changes shouldn't count as a new finding
+    let line: Vec<&str> = content[start..end].split_whitespace().collect();
+
+    format!("{:016x}", fnv1a(identifier.bytes().chain([0]).chain(line.join(" ").bytes())))
+}
+
+// FNV-1a: tiny, and stable across Rust versions unlike DefaultHasher
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint_of(content: &str, identifier: &str) -> String {
+        fingerprint(content, content.find(identifier).unwrap(), identifier)
+    }
+
+    #[test]
+    fn moving_or_reindenting_code_keeps_the_fingerprint() {
+        let original = fingerprint_of("def f():\n    tmp = load()\n    return tmp\n", "tmp");
+        assert_eq!(fingerprint_of("import os\n\n\ndef f():\n    tmp = load()\n    return tmp\n", "tmp"), original);
+        assert_eq!(fingerprint_of("class A:\n    def f(self):\n        tmp  =  load()\n        return tmp\n", "tmp"), original);
+        assert_eq!(fingerprint_of("tmp = load()", "tmp"), original);
+    }
+
+    #[test]
+    fn editing_the_line_or_the_name_changes_it() {
+        let original = fingerprint_of("tmp = load()\n", "tmp");
+        assert_ne!(fingerprint_of("tmp = load(path)\n", "tmp"), original);
+        // Same line text, different finding
+        assert_ne!(fingerprint("tmp = load()\n", 0, "load"), original);
+    }
+
+    #[test]
+    fn only_the_declaration_line_counts() {
+        let first = fingerprint_of("x = 1\ntmp = 2\ny = 3\n", "tmp");
+        assert_eq!(fingerprint_of("a = 0\ntmp = 2\nb = 4\n", "tmp"), first);
+    }
+}
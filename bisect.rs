@@ -0,0 +1,238 @@
+use std::io;
+use std::path::PathBuf;
+
+use bad_variable_changer::{check_rewrite, detect_language, modifyfile, LanguagePattern, Rewriter, Scanner};
+
+use crate::journal::Journal;
+use crate::{shell_command, RunReplacements};
+
+// How much of a rejecting command's output goes into the report
+const MAX_OUTPUT_LINES: usize = 20;
+
+// What is accepted or rejected as a whole
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Granularity {
+    Identifier,
+    File,
+}
+
+impl Granularity {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "identifier" => Some(Granularity::Identifier),
+            "file" => Some(Granularity::File),
+            _ => None,
+        }
+    }
+}
+
+struct SourceFile {
+    path: PathBuf,
+    language: LanguagePattern,
+    original: String,
+    // What is on disk right now
+    current: String,
+    renames: Vec<(String, String)>,
+}
+
+// One or more renames in a single file
+struct Unit {
+    file: usize,
+    renames: Vec<usize>,
+}
+
+struct Bisection<'a> {
+    files: Vec<SourceFile>,
+    command: &'a str,
+    backup: bool,
+    journal: &'a mut Journal,
+    // (file, rename) pairs currently accepted
+    accepted: Vec<(usize, usize)>,
+    rejected: Vec<(usize, Vec<usize>, String)>,
+    runs: usize,
+}
+
+// Applies the renames for files in units, running command to find the ones that keep
+// it passing: a group that fails is split in half until each failing unit stands alone.
+// Returns the renames kept; the files on disk end up with exactly those applied.
+pub fn run(
+    file_paths: &[PathBuf],
+    rewriter: &mut Rewriter,
+    scanner: &Scanner,
+    command: &str,
+    granularity: Granularity,
+    backup: bool,
+    mut journal: Journal,
+) -> io::Result<RunReplacements> {
+    let mut files = Vec::new();
+    for path in file_paths {
+        let Some(language) = detect_language(path) else {
+            continue;
+        };
+        let original = modifyfile::read_file(path.to_str().unwrap())?;
+        let rewrite = rewriter.rewrite(path, &original, &language, scanner);
+        crate::print_warnings(&rewrite.warnings);
+        let renames = rewrite.replacements;
+        if !renames.is_empty() {
+            files.push(SourceFile { path: path.clone(), language, current: original.clone(), original, renames });
+        }
+    }
+
+    let units: Vec<Unit> = files
+        .iter()
+        .enumerate()
+        .flat_map(|(file, source)| match granularity {
+            Granularity::Identifier => (0..source.renames.len()).map(|rename| Unit { file, renames: vec![rename] }).collect(),
+            Granularity::File => vec![Unit { file, renames: (0..source.renames.len()).collect() }],
+        })
+        .collect();
+    let total: usize = files.iter().map(|source| source.renames.len()).sum();
+    if total == 0 {
+        journal.commit()?;
+        println!("No bad variables found.");
+        return Ok(Vec::new());
+    }
+
+    let mut bisection = Bisection { files, command, backup, journal: &mut journal, accepted: Vec::new(), rejected: Vec::new(), runs: 0 };
+
+    // Nothing can be learned from a command that fails before anything is renamed
+    println!("Checking that `{}` passes before renaming anything...", command);
+    if let Err(output) = bisection.verify()? {
+        journal.commit()?;
+        println!("The verify command already fails on the untouched files; nothing was renamed.");
+        print_output(&output);
+        std::process::exit(1);
+    }
+
+    println!("Bisecting {} rename(s) in {} file(s)...", total, bisection.files.len());
+    let all: Vec<&Unit> = units.iter().collect();
+    bisection.test(&all)?;
+
+    let Bisection { files, accepted, rejected, runs, .. } = bisection;
+    journal.commit()?;
+
+    println!();
+    println!("Kept {} of {} rename(s) after {} run(s) of `{}`.", accepted.len(), total, runs, command);
+    if !rejected.is_empty() {
+        println!("Rejected:");
+        for (file, renames, output) in &rejected {
+            let source = &files[*file];
+            let renames: Vec<String> = renames.iter().map(|&r| format!("{} -> {}", source.renames[r].0, source.renames[r].1)).collect();
+            println!("  {}: {}", source.path.display(), renames.join(", "));
+            print_output(output);
+        }
+    }
+
+    Ok(files
+        .iter()
+        .enumerate()
+        .map(|(file, source)| {
+            let kept = source_renames(source, &accepted, file, &[]);
+            (source.path.display().to_string(), kept)
+        })
+        .collect())
+}
+
+impl Bisection<'_> {
+    fn test(&mut self, units: &[&Unit]) -> io::Result<()> {
+        let candidate: Vec<(usize, usize)> = units.iter().flat_map(|unit| unit.renames.iter().map(|&r| (unit.file, r))).collect();
+        // A candidate that would break a file's structure is rejected without running anything
+        let verdict = match self.write(&candidate)? {
+            Some(reason) => Err(reason),
+            None => self.verify()?,
+        };
+        match verdict {
+            Ok(()) => {
+                println!("  ok: {}", self.describe(units));
+                self.accepted.extend(candidate);
+            }
+            Err(output) if units.len() == 1 => {
+                println!("  rejected: {}", self.describe(units));
+                self.rejected.push((units[0].file, units[0].renames.clone(), output));
+                // Leave the files as the accepted renames alone would have them
+                self.write(&[])?;
+            }
+            Err(_) => {
+                let (first, second) = units.split_at(units.len() / 2);
+                self.test(first)?;
+                self.test(second)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Rewrites every file to its original with the accepted renames plus candidate applied.
+    // Writes nothing and returns the reason if that would change any file's structure.
+    fn write(&mut self, candidate: &[(usize, usize)]) -> io::Result<Option<String>> {
+        let mut wanted = Vec::new();
+        for (file, source) in self.files.iter().enumerate() {
+            let renames = source_renames(source, &self.accepted, file, candidate);
+            let rewritten = Rewriter::apply(&source.original, &source.language, &renames);
+            if let Err(reason) = check_rewrite(&source.original, &rewritten, &source.language) {
+                return Ok(Some(format!("{}: {}", source.path.display(), reason)));
+            }
+            wanted.push(rewritten);
+        }
+
+        for (source, wanted) in self.files.iter_mut().zip(wanted) {
+            if wanted == source.current {
+                continue;
+            }
+            if self.backup {
+                let backup_path = PathBuf::from(format!("{}.backup", source.path.display()));
+                if !backup_path.exists() {
+                    self.journal.record(&backup_path)?;
+                    modifyfile::write_file(backup_path.to_str().unwrap(), &source.original)?;
+                }
+            }
+            self.journal.record(&source.path)?;
+            modifyfile::write_file(source.path.to_str().unwrap(), &wanted)?;
+            source.current = wanted;
+        }
+        Ok(None)
+    }
+
+    // Err holds the command's output when it fails
+    fn verify(&mut self) -> io::Result<Result<(), String>> {
+        self.runs += 1;
+        let output = shell_command(self.command).output()?;
+        if output.status.success() {
+            return Ok(Ok(()));
+        }
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(Err(text))
+    }
+
+    fn describe(&self, units: &[&Unit]) -> String {
+        let renames: usize = units.iter().map(|unit| unit.renames.len()).sum();
+        match units {
+            [unit] => {
+                let source = &self.files[unit.file];
+                let names: Vec<&str> = unit.renames.iter().map(|&r| source.renames[r].0.as_str()).collect();
+                format!("{} in {}", names.join(", "), source.path.display())
+            }
+            _ => format!("{} rename(s) together", renames),
+        }
+    }
+}
+
+// This file's renames that are accepted or in candidate, in the order they were proposed
+fn source_renames(source: &SourceFile, accepted: &[(usize, usize)], file: usize, candidate: &[(usize, usize)]) -> Vec<(String, String)> {
+    (0..source.renames.len())
+        .filter(|&r| accepted.contains(&(file, r)) || candidate.contains(&(file, r)))
+        .map(|r| source.renames[r].clone())
+        .collect()
+}
+
+// The last lines of a command's output, indented under the rename it rejected
+fn print_output(output: &str) {
+    let lines: Vec<&str> = output.lines().collect();
+    let shown = &lines[lines.len().saturating_sub(MAX_OUTPUT_LINES)..];
+    if shown.len() < lines.len() {
+        println!("    | ... ({} earlier line(s))", lines.len() - shown.len());
+    }
+    for line in shown {
+        println!("    | {}", line);
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::baseline;
+use crate::language_patterns::LanguagePattern;
+use crate::rules::RuleProfile;
+
+// Created in the working directory unless told otherwise
+pub const DEFAULT_CACHE_DIR: &str = ".badvars-cache";
+
+// Part of every key; bump it when what counts as a declaration, or how it scores, changes
+const FORMAT: u32 = 6;
+
+// Each file's bad declarations, after the profile, threshold and `badvars:` comments
+// have had their say, stored under a key made of everything that can change them:
+// the tool version, those settings, the language and the file's content. An edited
+// file simply gets a new key, so entries never need invalidating.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Cache { dir: dir.into() }
+    }
+
+    pub fn key(content: &str, language: &LanguagePattern, profile: RuleProfile, threshold: u32) -> String {
+        let settings = format!("{}\0{}\0{:?}\0{:?}\0{}\0", env!("CARGO_PKG_VERSION"), FORMAT, language, profile, threshold);
+        format!("{:016x}", baseline::fnv1a(settings.bytes().chain(content.bytes())))
+    }
+
+    // (offset, name) of each cached declaration; None on a miss or an unreadable entry
+    pub fn get(&self, key: &str) -> Option<Vec<(usize, String)>> {
+        let content = fs::read_to_string(self.entry_path(key)).ok()?;
+        let mut declarations = Vec::new();
+        for line in content.lines().filter(|line| !line.starts_with('#')) {
+            let (offset, name) = line.split_once('\t')?;
+            declarations.push((offset.parse().ok()?, name.to_string()));
+        }
+        Some(declarations)
+    }
+
+    pub fn put(&self, key: &str, declarations: &[(usize, &str)]) -> io::Result<()> {
+        if !self.dir.is_dir() {
+            fs::create_dir_all(&self.dir)?;
+            // Keep the cache out of version control without asking anyone to edit .gitignore
+            fs::write(self.dir.join(".gitignore"), "*\n")?;
+        }
+        let mut entry = String::from("# offset\tname\n");
+        for (offset, name) in declarations {
+            entry.push_str(&format!("{}\t{}\n", offset, name));
+        }
+        // Write then rename, so a concurrent run never reads half an entry
+        let temporary = self.dir.join(format!("{}.{}.tmp", key, std::process::id()));
+        fs::write(&temporary, entry)?;
+        fs::rename(&temporary, self.entry_path(key))
+    }
+
+    // Removes the whole cache directory; returns how many entries were in it
+    pub fn clean(dir: &Path) -> io::Result<usize> {
+        if !dir.is_dir() {
+            return Ok(0);
+        }
+        let entries = fs::read_dir(dir)?
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "entry"))
+            .count();
+        fs::remove_dir_all(dir)?;
+        Ok(entries)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.entry", key))
+    }
+}
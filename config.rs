@@ -0,0 +1,73 @@
+use std::io;
+use std::path::Path;
+
+use bad_variable_changer::modifyfile;
+use bad_variable_changer::naming;
+
+// Looked for in the working directory when --config isn't given
+pub const DEFAULT_CONFIG: &str = ".badvars.conf";
+
+// Defaults read from a config file of `key = value` lines; command-line
+// options override every one of them
+#[derive(Debug, Default)]
+pub struct Config {
+    pub theme: Option<String>,
+    pub strategy: Option<String>,
+    pub profile: Option<String>,
+    pub threshold: Option<u32>,
+    pub seed: Option<u64>,
+}
+
+impl Config {
+    // A missing default config is fine; a missing --config file is not
+    pub fn discover(config_path: Option<&str>) -> io::Result<Self> {
+        match config_path {
+            Some(path) => Config::load(path),
+            None if Path::new(DEFAULT_CONFIG).is_file() => Config::load(DEFAULT_CONFIG),
+            None => Ok(Config::default()),
+        }
+    }
+
+    pub fn load(config_path: &str) -> io::Result<Self> {
+        let content = modifyfile::read_file(config_path)?;
+        let invalid = |line_no: usize, message: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", config_path, line_no + 1, message))
+        };
+
+        let mut config = Config::default();
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(line_no, "expected key = value".to_string()));
+            };
+            let value = value.trim().to_string();
+            match key.trim() {
+                "theme" => config.theme = Some(theme_path(config_path, value)),
+                "strategy" => config.strategy = Some(value),
+                "profile" => config.profile = Some(value),
+                "threshold" => {
+                    config.threshold = Some(value.parse().map_err(|_| invalid(line_no, "threshold must be a non-negative integer".to_string()))?)
+                }
+                "seed" => {
+                    config.seed = Some(value.parse().map_err(|_| invalid(line_no, "seed must be a non-negative integer".to_string()))?)
+                }
+                other => return Err(invalid(line_no, format!("unknown key '{}'", other))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+// Word list files are relative to the config file, not the working directory
+fn theme_path(config_path: &str, theme: String) -> String {
+    if naming::THEMES.contains(&theme.as_str()) || Path::new(&theme).is_absolute() {
+        return theme;
+    }
+    match Path::new(config_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(theme).display().to_string(),
+        _ => theme,
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+// Inclusive 1-based line ranges, keyed by canonical file path
+pub type ChangedLines = HashMap<PathBuf, Vec<(usize, usize)>>;
+
+const HOOK_MARKER: &str = "# Installed by bad_variable_changer install-hook";
+
+// Which changes to look at: what is staged, or everything since a revision
+pub enum ChangeSet<'a> {
+    Staged,
+    Since(&'a str),
+}
+
+impl ChangeSet<'_> {
+    fn diff_args(&self) -> Vec<&str> {
+        match self {
+            ChangeSet::Staged => vec!["diff", "--cached"],
+            ChangeSet::Since(rev) => vec!["diff", rev],
+        }
+    }
+}
+
+fn git(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn repo_root() -> io::Result<PathBuf> {
+    Ok(PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim()))
+}
+
+// Added, copied, modified or renamed files; deleted files have nothing left to check
+pub fn changed_files(changes: &ChangeSet) -> io::Result<Vec<PathBuf>> {
+    let root = repo_root()?;
+    let mut args = changes.diff_args();
+    args.extend(["--name-only", "--diff-filter=ACMR"]);
+    Ok(git(&args)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| root.join(line))
+        .collect())
+}
+
+pub fn changed_lines(changes: &ChangeSet) -> io::Result<ChangedLines> {
+    let root = repo_root()?;
+    let mut args = changes.diff_args();
+    args.extend(["--unified=0", "--no-color", "--no-ext-diff"]);
+    Ok(parse_unified_diff(&git(&args)?, &root))
+}
+
+// Reads a unified diff from a file, or from stdin when source is "-" (e.g. `git diff | ... --diff -`)
+pub fn read_diff(source: &str) -> io::Result<ChangedLines> {
+    let mut diff = String::new();
+    if source == "-" {
+        io::stdin().read_to_string(&mut diff)?;
+    } else {
+        diff = fs::read_to_string(source)?;
+    }
+    // git prints paths relative to the repository root; plain `diff -u` relative to where it ran
+    let root = match repo_root() {
+        Ok(root) => root,
+        Err(_) => std::env::current_dir()?,
+    };
+    Ok(parse_unified_diff(&diff, &root))
+}
+
+// Collects the lines each file gained: the `+` lines of each hunk, numbered from its `@@` header
+pub fn parse_unified_diff(diff: &str, root: &Path) -> ChangedLines {
+    let mut changed = ChangedLines::new();
+    let mut current: Option<PathBuf> = None;
+    // The next line of the new file, and how many old and new lines the hunk still has
+    let (mut line_number, mut old_left, mut new_left) = (0, 0usize, 0usize);
+
+    for line in diff.lines() {
+        if old_left > 0 || new_left > 0 {
+            match line.chars().next() {
+                Some('+') => {
+                    if let Some(file) = &current {
+                        let ranges = changed.entry(file.clone()).or_default();
+                        match ranges.last_mut() {
+                            Some(last) if last.1 + 1 == line_number => last.1 = line_number,
+                            _ => ranges.push((line_number, line_number)),
+                        }
+                    }
+                    line_number += 1;
+                    new_left = new_left.saturating_sub(1);
+                    continue;
+                }
+                Some('-') => {
+                    old_left = old_left.saturating_sub(1);
+                    continue;
+                }
+                // Some tools strip the space off empty context lines
+                Some(' ') | None => {
+                    line_number += 1;
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                    continue;
+                }
+                Some('\\') => continue,
+                // A hunk shorter than its header says; read the line as a header instead
+                _ => (old_left, new_left) = (0, 0),
+            }
+        }
+
+        if let Some(target) = line.strip_prefix("+++ ") {
+            // `diff -u` appends a tab and timestamp after the path
+            let target = target.split('\t').next().unwrap_or(target).trim();
+            current = match target {
+                "/dev/null" => None,
+                path => {
+                    let path = path.strip_prefix("b/").filter(|_| !root.join(path).exists()).unwrap_or(path);
+                    Some(canonical(&root.join(path)))
+                }
+            };
+        } else if line.starts_with("@@ ") {
+            if let Some((old_count, new_start, new_count)) = parse_hunk_header(line) {
+                (line_number, old_left, new_left) = (new_start, old_count, new_count);
+            }
+        }
+    }
+
+    changed
+}
+
+// "@@ -12,3 +14,5 @@" -> Some((3, 14, 5)): the old line count, then where the new lines start and how many there are
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = line.split_whitespace().skip(1);
+    let (_, old_count) = parse_range(parts.next()?.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(parts.next()?.strip_prefix('+')?)?;
+    Some((old_count, new_start, new_count))
+}
+
+// "14,5" -> (14, 5); a range without a count is one line long
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let mut numbers = range.splitn(2, ',');
+    let start = numbers.next()?.parse().ok()?;
+    let count = match numbers.next() {
+        Some(count) => count.parse().ok()?,
+        None => 1,
+    };
+    Some((start, count))
+}
+
+// What the index holds for path, i.e. what is about to be committed
+pub fn staged_content(path: &Path) -> io::Result<String> {
+    let root = canonical(&repo_root()?);
+    let path = canonical(path);
+    let relative = path.strip_prefix(&root).unwrap_or(&path);
+    git(&["show", &format!(":{}", relative.display())])
+}
+
+// A path that doesn't exist (any more) at least loses its `./` parts
+pub fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.components().filter(|part| *part != Component::CurDir).collect())
+}
+
+pub fn install_hook(force: bool) -> io::Result<PathBuf> {
+    let hooks_dir = PathBuf::from(git(&["rev-parse", "--git-path", "hooks"])?.trim());
+    let hook_path = hooks_dir.join("pre-commit");
+
+    if hook_path.exists() && !force {
+        let existing = fs::read_to_string(&hook_path).unwrap_or_default();
+        if !existing.contains(HOOK_MARKER) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists; use --force to overwrite it", hook_path.display()),
+            ));
+        }
+    }
+
+    fs::create_dir_all(&hooks_dir)?;
+    let script = format!(
+        "#!/bin/sh\n{}\nexec bad_variable_changer --check --staged --changed-lines\n",
+        HOOK_MARKER
+    );
+    fs::write(&hook_path, script)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(hook_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines_of(diff: &str) -> Vec<(usize, usize)> {
+        let root = Path::new("/nonexistent");
+        parse_unified_diff(diff, root).remove(&root.join("a.py")).unwrap_or_default()
+    }
+
+    #[test]
+    fn only_added_lines_count_not_context() {
+        let diff = "\
+diff --git a/a.py b/a.py
+--- a/a.py
++++ b/a.py
+@@ -10,7 +10,8 @@ def f():
+     one = 1
+     two = 2
+-    three = 3
++    d = 3
++    e = 4
+     four = 4
+ 
+     five = 5
+@@ -30,2 +31,3 @@
+ x = 1
++y = 2
+ z = 3
+";
+        assert_eq!(lines_of(diff), vec![(12, 13), (32, 32)]);
+    }
+
+    #[test]
+    fn unified_zero_and_pure_deletions() {
+        let diff = "\
+--- a/a.py
++++ b/a.py
+@@ -3 +3 @@
+-a = 1
++b = 1
+@@ -8,2 +7,0 @@
+-c = 1
+-d = 1
+@@ -20,0 +19,2 @@
++++x
++e = 2
+\\ No newline at end of file
+";
+        assert_eq!(lines_of(diff), vec![(3, 3), (19, 20)]);
+    }
+
+    #[test]
+    fn deleted_files_and_other_roots() {
+        let diff = "\
+--- a/gone.py
++++ /dev/null
+@@ -1,2 +0,0 @@
+-a = 1
+-b = 2
+--- a.py.orig\t2024-01-01 00:00:00
++++ a.py\t2024-01-01 00:00:01
+@@ -1 +1,2 @@
+ a = 1
++b = 2
+";
+        let changed = parse_unified_diff(diff, Path::new("/nonexistent"));
+        assert_eq!(changed.len(), 1);
+        assert_eq!(lines_of(diff), vec![(2, 2)]);
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::{self, BufRead, Write};
+
+use regex::Regex;
+
+// How many usages to print under each prompt before eliding the rest
+const MAX_SHOWN_USES: usize = 8;
+
+enum Choice {
+    Accept,
+    Reject,
+    Edit(String),
+    AcceptAll,
+    SkipFile,
+}
+
+// Walks the user through each proposed rename and returns only the ones they kept.
+// `is_taken` reports why a name can't be used, if it can't; a taken proposal is asked
+// about again, and skipped when accepting the rest.
+pub fn confirm_replacements<F>(
+    content: &str,
+    proposed: &[(String, String)],
+    is_taken: F,
+) -> io::Result<Vec<(String, String)>>
+where
+    F: Fn(&str, &[(String, String)]) -> Option<String>,
+{
+    let stdin = io::stdin();
+    confirm_from(&mut stdin.lock(), content, proposed, is_taken)
+}
+
+fn confirm_from<F>(
+    input: &mut impl BufRead,
+    content: &str,
+    proposed: &[(String, String)],
+    is_taken: F,
+) -> io::Result<Vec<(String, String)>>
+where
+    F: Fn(&str, &[(String, String)]) -> Option<String>,
+{
+    let mut chosen: Vec<(String, String)> = Vec::new();
+    let mut accept_rest = false;
+
+    for (old, new) in proposed {
+        if accept_rest {
+            match is_taken(new, &chosen) {
+                Some(reason) => println!("  Keeping {}: {}", old, reason),
+                None => chosen.push((old.clone(), new.clone())),
+            }
+            continue;
+        }
+
+        println!();
+        println!("  {} -> {}", old, new);
+        print_occurrences(content, old);
+
+        let clash = is_taken(new, &chosen);
+        loop {
+            match prompt(input)? {
+                Choice::Accept | Choice::AcceptAll if clash.is_some() => {
+                    println!("  Can't use {}: {}", new, clash.as_deref().unwrap_or_default());
+                    continue;
+                }
+                Choice::Accept => chosen.push((old.clone(), new.clone())),
+                Choice::Reject => {}
+                Choice::AcceptAll => {
+                    chosen.push((old.clone(), new.clone()));
+                    accept_rest = true;
+                }
+                Choice::SkipFile => return Ok(Vec::new()),
+                Choice::Edit(edited) => match is_taken(&edited, &chosen) {
+                    Some(reason) => println!("  Keeping {}: {}", old, reason),
+                    None => chosen.push((old.clone(), edited)),
+                },
+            }
+            break;
+        }
+    }
+
+    Ok(chosen)
+}
+
+fn print_occurrences(content: &str, name: &str) {
+    let re = match Regex::new(&format!(r"\b{}\b", regex::escape(name))) {
+        Ok(re) => re,
+        Err(_) => return,
+    };
+    let lines: Vec<(usize, &str)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| re.is_match(line))
+        .collect();
+
+    for (shown, (line_no, line)) in lines.iter().enumerate() {
+        if shown == MAX_SHOWN_USES {
+            println!("      ... and {} more", lines.len() - MAX_SHOWN_USES);
+            break;
+        }
+        let label = if shown == 0 { "declared" } else { "used" };
+        println!("    {:>8} {:>5}: {}", label, line_no + 1, line.trim_end());
+    }
+}
+
+fn prompt(input: &mut impl BufRead) -> io::Result<Choice> {
+    let identifier = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+    loop {
+        print!("  Rename? [y]es, [n]o, [e]dit, [a]ccept all, [s]kip file: ");
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            // stdin closed: treat it like skipping the rest of the file
+            return Ok(Choice::SkipFile);
+        }
+        match answer.trim() {
+            "y" | "yes" | "" => return Ok(Choice::Accept),
+            "n" | "no" => return Ok(Choice::Reject),
+            "a" | "all" => return Ok(Choice::AcceptAll),
+            "s" | "skip" => return Ok(Choice::SkipFile),
+            "e" | "edit" => {
+                print!("  New name: ");
+                io::stdout().flush()?;
+                let mut edited = String::new();
+                input.read_line(&mut edited)?;
+                let edited = edited.trim();
+                if identifier.is_match(edited) {
+                    return Ok(Choice::Edit(edited.to_string()));
+                }
+                println!("  '{}' is not a valid identifier", edited);
+            }
+            other => println!("  Unknown answer '{}'", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn taken(name: &str, chosen: &[(String, String)]) -> Option<String> {
+        chosen.iter().find(|(_, new)| new == name).map(|(old, _)| format!("{} is already renamed to it", old))
+    }
+
+    fn proposals() -> Vec<(String, String)> {
+        vec![("tmp".to_string(), "alfa".to_string()), ("x".to_string(), "yOurMom".to_string())]
+    }
+
+    #[test]
+    fn accepting_a_name_an_edit_took_asks_again() {
+        let mut input = "e\nyOurMom\ny\ne\nbravo\n".as_bytes();
+        let chosen = confirm_from(&mut input, "tmp = x\n", &proposals(), taken).unwrap();
+        assert_eq!(chosen, [("tmp".to_string(), "yOurMom".to_string()), ("x".to_string(), "bravo".to_string())]);
+    }
+
+    #[test]
+    fn accepting_the_rest_skips_a_name_an_edit_took() {
+        let mut proposed = proposals();
+        proposed.insert(1, ("y".to_string(), "charlie".to_string()));
+        let mut input = "e\nyOurMom\na\n".as_bytes();
+        let chosen = confirm_from(&mut input, "tmp = x + y\n", &proposed, taken).unwrap();
+        assert_eq!(chosen, [("tmp".to_string(), "yOurMom".to_string()), ("y".to_string(), "charlie".to_string())]);
+    }
+}
@@ -0,0 +1,639 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::language_patterns::{BindingKind, LanguagePattern};
+use crate::lexer::{self, Token, TokenKind};
+
+// Keywords that name a type, and C#'s aliases that aren't keywords here
+const PRIMITIVES: &[&str] = &["boolean", "bool", "byte", "char", "double", "float", "int", "long", "short", "var", "object", "void"];
+
+// Contextual keywords that start statements rather than name types
+const NOT_TYPES: &[&str] = &["await", "yield", "nameof", "and", "or", "not", "when"];
+
+// Words before a declaration's or parameter's type: `final int x`, `private static readonly Foo f`,
+// `using var s`, `out int n`, `params string[] args`
+const MODIFIERS: &[&str] = &[
+    "final", "static", "public", "private", "protected", "abstract", "transient", "volatile", "synchronized", "native",
+    "strictfp", "default", "const", "readonly", "internal", "override", "virtual", "sealed", "extern", "unsafe", "new",
+    "async", "partial", "required", "ref", "scoped", "using", "await", "in", "out", "params", "this",
+];
+
+// Declaring one of these makes the `{` after it a body of members
+const TYPE_KEYWORDS: &[&str] = &["class", "interface", "enum", "record", "struct", "namespace"];
+
+// What an open bracket is
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    // A class, interface, enum, record, struct or namespace body, or an anonymous class
+    Members,
+    // A method body or any other block of statements
+    Code,
+    // C#'s `new Foo { X = 1 }`
+    Initializer,
+    // C#'s anonymous `new { x, Y = 1 }`
+    Anonymous,
+    // C#'s `x switch { ... }`, whose arms start with patterns
+    Arms,
+    // A `for` or `foreach` header
+    Loop,
+    Other,
+}
+
+// Every name bound in content: local variables including every declarator of
+// `int a = 1, b[] = {}` with any type (generic, array, qualified, C#'s nullable and
+// tuple types), `for` and `foreach` variables, try-with-resources and `using`
+// variables, parameters of methods, constructors and lambdas, `catch` variables,
+// `instanceof`, `is` and `case` patterns, and C#'s `out var x` and `var (a, b)`.
+// Fields, properties and record components are members, reached through `this.` or
+// from other files, so nothing is reported for a name declared as one; likewise for
+// a parameter C# code passes by name (`f(x: 1)`) or a variable an anonymous type is
+// built from (`new { count }`).
+pub(crate) fn bindings<'a>(content: &'a str, language: &LanguagePattern) -> Vec<(usize, BindingKind, &'a str)> {
+    let tokens = code_tokens(content, language);
+    let mut keep: HashSet<&str> = named_arguments(&tokens, language).into_iter().map(|i| tokens[i].text).collect();
+    let mut found = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        if starts_statement(&tokens, i) {
+            let members = brackets.last() == Some(&Bracket::Members);
+            let names = declaration(&tokens, i, language, members);
+            match brackets.last() {
+                Some(Bracket::Members) => keep.extend(names.iter().map(|name| name.text)),
+                Some(Bracket::Loop) => found.extend(names.iter().map(|name| (name.offset, BindingKind::LoopIndex, name.text))),
+                _ => found.extend(names.iter().map(|name| (name.offset, BindingKind::Variable, name.text))),
+            }
+        }
+        let entry = previous.is_some_and(|previous| previous.is("{") || previous.is(","));
+        match brackets.last() {
+            Some(Bracket::Arms) if entry => pattern_bindings(&tokens[i..], language, &mut found),
+            // `new { count }` names its member after the variable
+            Some(Bracket::Anonymous) if entry && tokens.get(i + 1).is_some_and(|next| next.is(",") || next.is("}")) => {
+                keep.insert(token.text);
+            }
+            _ => {}
+        }
+
+        if token.is("(") {
+            let close = lexer::matching(&tokens, i);
+            if previous.is_some_and(|previous| previous.is("catch")) {
+                // `catch (IOException | SQLException e)`
+                let name = tokens[i + 1..close].last().filter(|name| close - i > 2 && is_name(name, language));
+                found.extend(name.map(|name| (name.offset, BindingKind::ExceptionBinding, name.text)));
+            } else if declares_components(&tokens, i) {
+                keep.extend(parameters(&tokens[i + 1..close], language).iter().map(|name| name.text));
+            } else if is_parameter_list(&tokens, i, close, language) {
+                found.extend(parameters(&tokens[i + 1..close], language).iter().map(|name| (name.offset, BindingKind::Parameter, name.text)));
+            }
+        } else if (token.is("->") && *language == LanguagePattern::Java) || (token.is("=>") && *language == LanguagePattern::CSharp) {
+            if !matches!(brackets.last(), Some(Bracket::Members | Bracket::Arms)) {
+                lambda_parameters(&tokens, i, language, &mut found);
+            }
+        } else if token.is("instanceof") || token.is("is") || token.is("case") {
+            pattern_bindings(&tokens[i + 1..], language, &mut found);
+        } else if token.is("out") && *language == LanguagePattern::CSharp {
+            // `int.TryParse(s, out var n)`
+            if let Some(name) = type_end(&tokens, i + 1, language).and_then(|end| tokens.get(end)).filter(|name| is_name(name, language)) {
+                found.push((name.offset, BindingKind::Variable, name.text));
+            }
+        }
+
+        if token.opens() {
+            brackets.push(bracket(&tokens, i, language));
+        } else if token.closes() {
+            brackets.pop();
+        }
+    }
+    found.retain(|(_, _, name)| !keep.contains(name));
+    found
+}
+
+// Whether a statement may start at i: after `{`, `}` or `;`, after the `(` of a `for`,
+// `foreach`, `try`, `using` or `fixed`, or after a `case` or `default` label's `:`
+fn starts_statement(tokens: &[Token], i: usize) -> bool {
+    let Some(previous) = i.checked_sub(1).map(|j| &tokens[j]) else {
+        return true;
+    };
+    if previous.is("{") || previous.is("}") || previous.is(";") {
+        return true;
+    }
+    if previous.is("(") && i >= 2 {
+        let keyword = &tokens[i - 2];
+        return ["for", "foreach", "try", "using", "fixed"].iter().any(|header| keyword.is(header));
+    }
+    previous.is(":") && in_case_label(tokens, i - 1)
+}
+
+// Whether i is inside a `case` or `default` label, before its `:` or `->`
+fn in_case_label(tokens: &[Token], i: usize) -> bool {
+    let mut j = i;
+    while j > 0 {
+        let token = &tokens[j - 1];
+        if token.is("case") || token.is("default") {
+            return true;
+        }
+        if token.is("}") || token.opens() || [";", ":", "->", "=>"].iter().any(|stop| token.is(stop)) {
+            return false;
+        }
+        j = if token.closes() { lexer::opening(tokens, j - 1) } else { j - 1 };
+    }
+    false
+}
+
+// The names a declaration starting at start declares, after its modifiers and type:
+// each declarator's, up to the `;` or the bracket closing around it. Empty if the
+// statement there isn't a declaration. In a body of members a name may also start a
+// C# property, followed by `{` or `=>`.
+fn declaration<'a, 'b>(tokens: &'b [Token<'a>], start: usize, language: &LanguagePattern, members: bool) -> Vec<&'b Token<'a>> {
+    let mut i = start;
+    while tokens.get(i).is_some_and(|token| MODIFIERS.iter().any(|modifier| token.is(modifier))) {
+        i += 1;
+    }
+    if tokens.get(i).is_some_and(|token| token.is("var")) && tokens.get(i + 1).is_some_and(|token| token.is("(")) {
+        // C# deconstruction: `var (a, b) = pair`
+        let close = lexer::matching(tokens, i + 1);
+        return tokens[i + 2..close].iter().filter(|token| is_name(token, language)).collect();
+    }
+    let Some(mut i) = type_end(tokens, i, language) else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    while let Some(name) = tokens.get(i).filter(|name| is_name(name, language)) {
+        // C-style array declarators: `int x[] = ...`
+        let mut j = i + 1;
+        while tokens.get(j).is_some_and(|token| token.is("[")) && tokens.get(j + 1).is_some_and(|token| token.is("]")) {
+            j += 2;
+        }
+        let ends = ["=", ";", ",", ":", ")", "in"].iter().chain(if members { &["{", "=>"][..] } else { &[] }).any(|end| tokens.get(j).is_none_or(|next| next.is(end)));
+        if !ends {
+            break;
+        }
+        names.push(name);
+        // Past the initializer to the next declarator
+        match lexer::top_level(&tokens[j..], |token| token.is(",") || token.is(";")) {
+            Some(end) if tokens[j + end].is(",") => i = j + end + 1,
+            _ => break,
+        }
+    }
+    names
+}
+
+// Index just past the type starting at start, if one does: `int`, `String[]`,
+// `Map<String, List<Integer>>`, `java.util.List<? extends T>`, `String...`, and C#'s
+// `int?` and `(int, string)`
+fn type_end(tokens: &[Token], start: usize, language: &LanguagePattern) -> Option<usize> {
+    let first = tokens.get(start)?;
+    let mut i = if first.is("(") && *language == LanguagePattern::CSharp {
+        let close = lexer::matching(tokens, start);
+        for element in lexer::split(&tokens[start + 1..close]) {
+            // Each element a type, optionally named
+            let end = type_end(element, 0, language)?;
+            if end < element.len() && !(end + 1 == element.len() && is_name(&element[end], language)) {
+                return None;
+            }
+        }
+        close + 1
+    } else if is_type_name(first, language) {
+        start + 1
+    } else {
+        return None;
+    };
+    loop {
+        let Some(token) = tokens.get(i) else {
+            return Some(i);
+        };
+        let next = tokens.get(i + 1);
+        if (token.is(".") || token.is("::")) && next.is_some_and(|next| is_type_name(next, language)) {
+            i += 2;
+        } else if token.is("<") {
+            i = angle_end(tokens, i)? + 1;
+        } else if token.is("[") && next.is_some_and(|next| next.is("]") || next.is(",")) {
+            i = lexer::matching(tokens, i) + 1;
+        } else if token.is("...") || (token.is("?") && *language == LanguagePattern::CSharp) {
+            i += 1;
+        } else {
+            return Some(i);
+        }
+    }
+}
+
+// Index of the `>` closing the type arguments opened at open, if only a type can be in between
+fn angle_end(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is("<") {
+            depth += 1;
+        } else if token.is(">") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else if !(token.kind == TokenKind::Ident || [",", ".", "?", "[", "]", "&", "::", "(", ")"].iter().any(|allowed| token.is(allowed))) {
+            return None;
+        }
+    }
+    None
+}
+
+fn is_type_name(token: &Token, language: &LanguagePattern) -> bool {
+    token.kind == TokenKind::Ident
+        && (PRIMITIVES.contains(&token.text) || !lexer::is_keyword(language, token.text))
+        && !NOT_TYPES.contains(&token.text)
+}
+
+fn is_name(token: &Token, language: &LanguagePattern) -> bool {
+    token.kind == TokenKind::Ident && !lexer::is_keyword(language, token.text) && !NOT_TYPES.contains(&token.text) && token.text != "_"
+}
+
+// The names in a parameter list where each parameter has a type: `final Map<K, V> m,
+// String... rest`, C#'s `out int n, int x = 0`
+fn parameters<'a, 'b>(tokens: &'b [Token<'a>], language: &LanguagePattern) -> Vec<&'b Token<'a>> {
+    let mut names = Vec::new();
+    for parameter in lexer::split_parameters(tokens) {
+        let mut start = 0;
+        while parameter.get(start).is_some_and(|token| MODIFIERS.iter().any(|modifier| token.is(modifier))) {
+            start += 1;
+        }
+        let end = lexer::top_level(parameter, |token| token.is("=")).unwrap_or(parameter.len());
+        let typed = end > start + 1 && type_end(parameter, start, language) == Some(end - 1);
+        if let Some(name) = parameter.get(end.wrapping_sub(1)).filter(|name| typed && is_name(name, language)) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+// Whether the parentheses at open follow a method or constructor name and are followed
+// by its body: `void run(int n) {`, `Foo(int x) throws E {`, C#'s `int Twice(int x) => 2 * x;`
+fn is_parameter_list(tokens: &[Token], open: usize, close: usize, language: &LanguagePattern) -> bool {
+    let Some(name) = name_before(tokens, open) else {
+        return false;
+    };
+    if lexer::is_keyword(language, tokens[name].text) {
+        return false;
+    }
+    tokens.get(close + 1).is_some_and(|next| ["{", "throws", "=>", ":", "where"].iter().any(|body| next.is(body)))
+}
+
+// Whether the parentheses at open declare a Java record's components or a C# primary
+// constructor's parameters, which are the type's members: `record Point(int x, int y)`
+fn declares_components(tokens: &[Token], open: usize) -> bool {
+    name_before(tokens, open).is_some_and(|name| name > 0 && ["record", "class", "struct"].iter().any(|keyword| tokens[name - 1].is(keyword)))
+}
+
+// Index of the name before the `(` at open, past any type parameters: `foo(`, `Pair<A, B>(`
+fn name_before(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut j = open;
+    if j > 0 && tokens[j - 1].is(">") {
+        let mut depth = 0usize;
+        while j > 0 {
+            j -= 1;
+            if tokens[j].is(">") {
+                depth += 1;
+            } else if tokens[j].is("<") {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+    }
+    j.checked_sub(1).filter(|&name| tokens[name].kind == TokenKind::Ident)
+}
+
+// A lambda's parameters, before its arrow at arrow: `x ->`, `(a, b) ->`, `(int a, int b) ->`,
+// C#'s `async x =>`. Not a `case` label's constants, nor a pattern's `Type name` before it.
+fn lambda_parameters<'a>(tokens: &[Token<'a>], arrow: usize, language: &LanguagePattern, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    let Some(previous) = arrow.checked_sub(1).map(|j| &tokens[j]) else {
+        return;
+    };
+    if previous.is(")") {
+        let open = lexer::opening(tokens, arrow - 1);
+        // A call or a record pattern rather than a parameter list
+        if open > 0 && (tokens[open - 1].is(">") || is_name(&tokens[open - 1], language)) {
+            return;
+        }
+        for parameter in lexer::split_parameters(&tokens[open + 1..arrow - 1]) {
+            let untyped = parameter.len() == 1;
+            if let Some(name) = parameter.last().filter(|name| is_name(name, language)) {
+                if untyped || type_end(parameter, parameter.iter().take_while(|token| MODIFIERS.iter().any(|modifier| token.is(modifier))).count(), language) == Some(parameter.len() - 1) {
+                    found.push((name.offset, BindingKind::LambdaParam, name.text));
+                }
+            }
+        }
+    } else if is_name(previous, language) && !in_case_label(tokens, arrow - 1) {
+        // `Type name =>` is a pattern
+        let typed = arrow >= 2 && {
+            let before = &tokens[arrow - 2];
+            let word = before.kind == TokenKind::Ident && !["async", "static", "return"].iter().any(|word| before.is(word));
+            word || [">", "]", "?"].iter().any(|end| before.is(end))
+        };
+        if !typed {
+            found.push((previous.offset, BindingKind::LambdaParam, previous.text));
+        }
+    }
+}
+
+// The names a pattern at the start of tokens binds: `String s`, `Point(int x, var y)`,
+// `var (a, b)`, C#'s `Point { X: 0 } p`; constants, `null` and C#'s `not null` and `> 0`
+// bind nothing
+fn pattern_bindings<'a>(tokens: &[Token<'a>], language: &LanguagePattern, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    let start = tokens.iter().take_while(|token| token.is("not") || token.is("final")).count();
+    let end = match tokens.get(start) {
+        Some(token) if token.is("{") => start,
+        _ => match type_end(tokens, start, language) {
+            // Unless the type was the condition of a `?:`
+            Some(end) if !tokens[end - 1].is("?") => end,
+            _ => return,
+        },
+    };
+    let Some(next) = tokens.get(end) else {
+        return;
+    };
+    if next.is("{") {
+        // A C# property pattern: `Point { X: 0 } p`
+        let close = lexer::matching(tokens, end);
+        if let Some(name) = tokens.get(close + 1).filter(|name| is_name(name, language)) {
+            found.push((name.offset, BindingKind::PatternBinding, name.text));
+        }
+    } else if next.is("(") {
+        // A record or positional pattern, each of its parts a pattern too
+        let close = lexer::matching(tokens, end);
+        let var = tokens[start].is("var");
+        for part in lexer::split(&tokens[end + 1..close]) {
+            match part {
+                [name] if var && is_name(name, language) => found.push((name.offset, BindingKind::PatternBinding, name.text)),
+                _ => pattern_bindings(part, language, found),
+            }
+        }
+        if let Some(name) = tokens.get(close + 1).filter(|name| is_name(name, language)) {
+            found.push((name.offset, BindingKind::PatternBinding, name.text));
+        }
+    } else if is_name(next, language) {
+        found.push((next.offset, BindingKind::PatternBinding, next.text));
+    }
+}
+
+// Indexes of the names of the arguments C# code passes by name: the x in `f(x: 1)`
+fn named_arguments(tokens: &[Token], language: &LanguagePattern) -> Vec<usize> {
+    if *language != LanguagePattern::CSharp {
+        return Vec::new();
+    }
+    (1..tokens.len().saturating_sub(1))
+        .filter(|&i| tokens[i].kind == TokenKind::Ident && (tokens[i - 1].is("(") || tokens[i - 1].is(",")) && tokens[i + 1].is(":"))
+        .filter(|&i| enclosing_paren(tokens, i))
+        .collect()
+}
+
+// Whether the innermost bracket around i is a `(`
+fn enclosing_paren(tokens: &[Token], i: usize) -> bool {
+    let mut j = i;
+    while j > 0 {
+        let token = &tokens[j - 1];
+        if token.opens() {
+            return token.is("(");
+        }
+        j = if token.closes() { lexer::opening(tokens, j - 1) } else { j - 1 };
+    }
+    false
+}
+
+// What the bracket at open is
+fn bracket(tokens: &[Token], open: usize, language: &LanguagePattern) -> Bracket {
+    let previous = open.checked_sub(1).map(|j| &tokens[j]);
+    let token = &tokens[open];
+    if token.is("(") {
+        let header = previous.is_some_and(|previous| previous.is("for") || previous.is("foreach"));
+        return if header { Bracket::Loop } else { Bracket::Other };
+    }
+    if !token.is("{") {
+        return Bracket::Other;
+    }
+    if previous.is_some_and(|previous| previous.is("switch")) && *language == LanguagePattern::CSharp {
+        return Bracket::Arms;
+    }
+    if is_type_body(tokens, open) {
+        return Bracket::Members;
+    }
+    match language {
+        LanguagePattern::CSharp if previous.is_some_and(|previous| previous.is("new")) => Bracket::Anonymous,
+        LanguagePattern::CSharp if follows_new(tokens, open) => Bracket::Initializer,
+        LanguagePattern::Java if previous.is_some_and(|previous| previous.is(")")) && follows_new(tokens, open) => Bracket::Members,
+        _ => Bracket::Code,
+    }
+}
+
+// Whether the `{` at open starts the body of a class, interface, enum, record, struct or namespace
+fn is_type_body(tokens: &[Token], open: usize) -> bool {
+    let mut j = open;
+    while j > 0 {
+        let token = &tokens[j - 1];
+        if token.is("}") || token.opens() || [";", "->", "=>", "="].iter().any(|stop| token.is(stop)) {
+            return false;
+        }
+        // Not `Foo.class`, nor a C# constraint `where T : class`
+        let declares = j < 2 || ![".", ":", ","].iter().any(|before| tokens[j - 2].is(before));
+        if TYPE_KEYWORDS.iter().any(|keyword| token.is(keyword)) && declares {
+            return true;
+        }
+        j = if token.closes() { lexer::opening(tokens, j - 1) } else { j - 1 };
+    }
+    false
+}
+
+// Whether the `{` at open follows `new` and a type, with or without arguments:
+// `new Foo() {`, `new Foo {`, `new {`
+fn follows_new(tokens: &[Token], open: usize) -> bool {
+    let mut j = open;
+    if j > 0 && tokens[j - 1].is(")") {
+        j = lexer::opening(tokens, j - 1);
+    }
+    while j > 0 && ((tokens[j - 1].kind == TokenKind::Ident && !tokens[j - 1].is("new")) || [".", "<", ">", ",", "?", "[", "]"].iter().any(|part| tokens[j - 1].is(part))) {
+        j -= 1;
+    }
+    j > 0 && tokens[j - 1].is("new")
+}
+
+// content's tokens without comments, line breaks, Java annotations, C# attributes and
+// C# preprocessor lines
+fn code_tokens<'a>(content: &'a str, language: &LanguagePattern) -> Vec<Token<'a>> {
+    let csharp = *language == LanguagePattern::CSharp;
+    let language = if csharp { &LanguagePattern::CSharp } else { &LanguagePattern::Java };
+    let tokens: Vec<Token> = lexer::tokenize(content, language).into_iter().filter(|token| token.kind != TokenKind::Comment).collect();
+    let mut code: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        let line_start = i == 0 || tokens[i - 1].kind == TokenKind::Newline;
+        if token.kind == TokenKind::Newline {
+            i += 1;
+        } else if csharp && token.is("#") && line_start {
+            // A preprocessor line: `#region Helpers`, `#if DEBUG`
+            i += tokens[i..].iter().position(|token| token.kind == TokenKind::Newline).unwrap_or(tokens.len() - i);
+        } else if !csharp && token.is("@") && tokens.get(i + 1).is_some_and(|next| next.kind == TokenKind::Ident && !next.is("interface")) {
+            // An annotation: `@Override`, `@SuppressWarnings("unchecked")`
+            i += 2;
+            while tokens.get(i).is_some_and(|token| token.is(".")) && tokens.get(i + 1).is_some_and(|next| next.kind == TokenKind::Ident) {
+                i += 2;
+            }
+            if tokens.get(i).is_some_and(|token| token.is("(")) {
+                i = lexer::matching(&tokens, i) + 1;
+            }
+        } else if csharp && token.is("[") && is_attribute(&code, &tokens, i) {
+            i = lexer::matching(&tokens, i) + 1;
+        } else {
+            code.push(token);
+            i += 1;
+        }
+    }
+    code
+}
+
+// Whether the `[` at open in tokens starts a C# attribute: `[Serializable]`, `[FromBody] Foo x`.
+// It comes where a declaration or parameter may start and a name or another attribute follows it.
+fn is_attribute(code: &[Token], tokens: &[Token], open: usize) -> bool {
+    let starts = code.last().is_none_or(|previous| ["(", ",", "{", "}", ";", "]"].iter().any(|before| previous.is(before)));
+    let close = lexer::matching(tokens, open);
+    let after = tokens.iter().skip(close + 1).find(|token| token.kind != TokenKind::Newline);
+    starts && after.is_some_and(|after| after.kind == TokenKind::Ident || after.is("["))
+}
+
+// Renames every use of each old name in code and in C# interpolated strings, leaving
+// alone members reached through `.` (`this.x`, `obj.x`) or `::`, method names, C#
+// named arguments (`f(x: 1)`), members set in C# initializers, comments and strings
+pub(crate) fn rename(content: &str, language: &LanguagePattern, replacements: &[(String, String)]) -> String {
+    let names: HashMap<&str, &str> = replacements.iter().map(|(old, new)| (old.as_str(), new.as_str())).collect();
+    let tokens = code_tokens(content, language);
+    let named: HashSet<usize> = named_arguments(&tokens, language).into_iter().collect();
+    let mut edits = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        let next = tokens.get(i + 1);
+        if token.opens() {
+            brackets.push(bracket(&tokens, i, language));
+            continue;
+        }
+        if token.closes() {
+            brackets.pop();
+            continue;
+        }
+        if token.kind != TokenKind::Ident {
+            continue;
+        }
+        let Some(new_name) = names.get(token.text) else {
+            continue;
+        };
+        let member = previous.is_some_and(|previous| previous.is(".") || previous.is("::"));
+        // Java keeps methods apart from variables; in C# a variable may hold a delegate
+        let method = next.is_some_and(|next| next.is("(")) && (*language == LanguagePattern::Java || brackets.last() == Some(&Bracket::Members));
+        if member || method || named.contains(&i) {
+            continue;
+        }
+        // A member being set: `new Foo { Name = name }`
+        let initializer = matches!(brackets.last(), Some(Bracket::Initializer | Bracket::Anonymous));
+        if initializer && previous.is_some_and(|previous| previous.is("{") || previous.is(",")) && next.is_some_and(|next| next.is("=")) {
+            continue;
+        }
+        edits.push((token.offset, token.text.len(), new_name.to_string()));
+    }
+    lexer::apply_edits(content, edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JAVA: &LanguagePattern = &LanguagePattern::Java;
+    const CSHARP: &LanguagePattern = &LanguagePattern::CSharp;
+
+    fn bound<'a>(content: &'a str, language: &LanguagePattern) -> Vec<(BindingKind, &'a str)> {
+        let mut found = bindings(content, language);
+        found.sort_by_key(|(offset, _, _)| *offset);
+        found.into_iter().map(|(_, kind, name)| (kind, name)).collect()
+    }
+
+    fn renamed(content: &str, language: &LanguagePattern, old: &str, new: &str) -> String {
+        rename(content, language, &[(old.to_string(), new.to_string())])
+    }
+
+    #[test]
+    fn fields_are_members_and_locals_are_bound() {
+        let content = "class A {\n  private int tmp = 0;\n  int run(final Map<K, V> m, String... rest) {\n    int a = 1, b[] = {};\n    for (int i = 0; i < a; i++) {}\n    for (var e : m.entrySet()) {}\n    try (var res = open()) {} catch (IOException ex) {}\n    if (o instanceof String s) {}\n    return this.tmp;\n  }\n}\n";
+        assert_eq!(
+            bound(content, JAVA),
+            [
+                (BindingKind::Parameter, "m"),
+                (BindingKind::Parameter, "rest"),
+                (BindingKind::Variable, "a"),
+                (BindingKind::Variable, "b"),
+                (BindingKind::LoopIndex, "i"),
+                (BindingKind::LoopIndex, "e"),
+                (BindingKind::Variable, "res"),
+                (BindingKind::ExceptionBinding, "ex"),
+                (BindingKind::PatternBinding, "s"),
+            ]
+        );
+        assert_eq!(bindings(content, JAVA).iter().map(|(offset, _, _)| *offset).min(), content.find("m,"));
+    }
+
+    #[test]
+    fn record_components_and_lambdas() {
+        let content = "record P(int x, int y) {}\nclass B { void f() { list.forEach((a, b) -> g(a)); run(c -> c); } }\n";
+        assert_eq!(
+            bound(content, JAVA),
+            [(BindingKind::LambdaParam, "a"), (BindingKind::LambdaParam, "b"), (BindingKind::LambdaParam, "c")]
+        );
+    }
+
+    #[test]
+    fn rename_leaves_fields_and_methods_alone() {
+        let content = "class A { int x; int x() { int y = x; return this.x + y + x(); } }\n";
+        assert_eq!(renamed(content, JAVA, "y", "x2"), "class A { int x; int x() { int x2 = x; return this.x + x2 + x(); } }\n");
+        let content = "void f(int tmp) { String s = \"tmp\" + \"\"\"\n    tmp\n    \"\"\" + tmp; }\n";
+        assert_eq!(renamed(content, JAVA, "tmp", "t"), "void f(int t) { String s = \"tmp\" + \"\"\"\n    tmp\n    \"\"\" + t; }\n");
+    }
+
+    #[test]
+    fn csharp_named_arguments_are_kept() {
+        let content = "class A {\n  int Sum(int count, int step) { return count * step; }\n  void G() { Sum(count: 1, step: 2); var total = Sum(1, 2); }\n}\n";
+        assert_eq!(bound(content, CSHARP), [(BindingKind::Variable, "total")]);
+        let content = "void G(int n) { F(n: n); }\n";
+        assert_eq!(renamed(content, CSHARP, "n", "count"), "void G(int count) { F(n: count); }\n");
+    }
+
+    #[test]
+    fn csharp_properties_out_vars_and_tuples() {
+        let content = "class A {\n  public int Tmp { get; set; }\n  void F() {\n    int.TryParse(s, out var n);\n    var (a, b) = P();\n    foreach (var item in xs) {}\n  }\n}\n";
+        assert_eq!(
+            bound(content, CSHARP),
+            [(BindingKind::Variable, "n"), (BindingKind::Variable, "a"), (BindingKind::Variable, "b"), (BindingKind::LoopIndex, "item")]
+        );
+    }
+
+    #[test]
+    fn csharp_interpolated_verbatim_and_raw_strings() {
+        let content = "void F(int x) { G($\"{x} x {{x}}\", @\"x\\\", $@\"{x}\", \"\"\"x\"\"\", $\"\"\"{x} x\"\"\"); }\n";
+        assert_eq!(
+            renamed(content, CSHARP, "x", "n"),
+            "void F(int n) { G($\"{n} x {{x}}\", @\"x\\\", $@\"{n}\", \"\"\"x\"\"\", $\"\"\"{n} x\"\"\"); }\n"
+        );
+    }
+
+    #[test]
+    fn every_prefix_of_a_file_being_typed_is_handled() {
+        let files = [
+            (JAVA, "@Entity\nrecord P(int x, int y) {}\nclass A<T extends Comparable<? super T>> {\n  private final Map<String, List<T>> tmp = new HashMap<>();\n  int run(final int[] xs, String... rest) throws IOException {\n    int a = 1, b[] = {};\n    for (int i = 0; i < a; i++) { tmp.put(\"k\", null); }\n    for (var e : xs) {}\n    try (var r = open()) {} catch (IOException | RuntimeException ex) {}\n    if (o instanceof P(int px, var py) && o instanceof String s) {}\n    list.forEach((c, d) -> g(c));\n    return switch (a) { case 1 -> b.length; default -> { yield 0; } };\n  }\n}\n"),
+            (CSHARP, "[Serializable]\nclass A {\n  public int Tmp { get; set; } = 0;\n  void F([FromBody] string s, out int n, params int[] rest) {\n    var (a, b) = P();\n    int.TryParse(s, out var m);\n    foreach (var item in rest) { var o = new { item, Y = 1 }; var q = new Foo { X = m }; }\n    var r = n switch { > 0 and var k => k, Point { X: 0 } p => p.Y, _ => 0 };\n    G(count: a, $\"{b} {{x}}\", @\"c:\\\", $\"\"\"{a}\"\"\");\n  }\n}\n"),
+        ];
+        let replacements = [("a".to_string(), "alpha".to_string()), ("tmp".to_string(), "total".to_string())];
+        for (language, content) in files {
+            for end in (0..=content.len()).filter(|&end| content.is_char_boundary(end)) {
+                bindings(&content[..end], language);
+                rename(&content[..end], language, &replacements);
+            }
+        }
+    }
+}
+
@@ -0,0 +1,539 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::language_patterns::{BindingKind, LanguagePattern};
+use crate::lexer::{self, Token, TokenKind};
+
+const JAVASCRIPT: &LanguagePattern = &LanguagePattern::JavaScript;
+
+const DECLARATIONS: &[&str] = &["let", "const", "var"];
+
+// Words that may come before a class member's name: `static async foo()`, `private readonly x`
+const MODIFIERS: &[&str] = &[
+    "static", "get", "set", "async", "public", "private", "protected", "readonly", "abstract", "declare", "override", "accessor",
+];
+
+// TypeScript constructor parameters marked with these are also fields: `constructor(private x: number)`
+const PARAMETER_PROPERTIES: &[&str] = &["public", "private", "protected", "readonly", "override"];
+
+// Tokens a `{` can follow when it opens an object rather than a block
+const BEFORE_OBJECT: &[&str] = &["return", "yield", "await", "typeof", "in", "of", "const", "let", "var", "default", "case", "void", "delete", "throw"];
+
+// Every name bound in content: `let`, `const` and `var` declarators including
+// destructuring with defaults and renames (`{ a: b = 1, ...rest }`), `for` loop
+// variables, every parameter of a function, method or arrow function, and `catch`
+// bindings. Type annotations, generics and optional markers are skipped. Nothing is
+// reported for exported declarations or constructor parameter properties, since
+// renaming those would rename what other code sees.
+pub(crate) fn bindings(content: &str) -> Vec<(usize, BindingKind, &str)> {
+    let tokens = code_tokens(content);
+    let mut found = Vec::new();
+    let mut keep: HashSet<&str> = HashSet::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        if previous.is_some_and(is_member_access) {
+            continue;
+        }
+        if DECLARATIONS.iter().any(|keyword| token.is(keyword)) && tokens.get(i + 1).is_some_and(starts_pattern) {
+            let in_loop = previous.is_some_and(|previous| previous.is("("))
+                && ((i >= 2 && tokens[i - 2].is("for")) || (i >= 3 && tokens[i - 2].is("await") && tokens[i - 3].is("for")));
+            let kind = if in_loop { BindingKind::LoopIndex } else { BindingKind::Variable };
+            let mut declared = Vec::new();
+            declarators(&tokens[i + 1..], kind, &mut declared);
+            if previous.is_some_and(|previous| previous.is("export")) {
+                keep.extend(declared.iter().map(|(_, _, name)| *name));
+            }
+            found.extend(declared);
+        } else if token.is("(") {
+            let close = lexer::matching(&tokens, i);
+            if previous.is_some_and(|previous| previous.is("catch")) {
+                let end = lexer::top_level(&tokens[i + 1..close], |token| token.is(":")).map_or(close, |end| i + 1 + end);
+                pattern_bindings(&tokens[i + 1..end], BindingKind::ExceptionBinding, &mut found);
+            } else if let Some(kind) = parameter_list(&tokens, i, close) {
+                for parameter in lexer::split_parameters(&tokens[i + 1..close]) {
+                    let (property, pattern) = parameter_pattern(parameter);
+                    if property {
+                        let mut fields = Vec::new();
+                        pattern_bindings(pattern, kind, &mut fields);
+                        keep.extend(fields.iter().map(|(_, _, name)| *name));
+                    } else {
+                        pattern_bindings(pattern, kind, &mut found);
+                    }
+                }
+            }
+        } else if token.kind == TokenKind::Ident
+            && tokens.get(i + 1).is_some_and(|next| next.is("=>"))
+            && !lexer::is_keyword(JAVASCRIPT, token.text)
+            && !previous.is_some_and(|previous| [":", "|", "&", "is"].iter().any(|before| previous.is(before)))
+        {
+            // `x => ...`, unless x ends an arrow function's return type: `(a): Foo => ...`
+            found.push((token.offset, BindingKind::LambdaParam, token.text));
+        }
+    }
+    found.retain(|(_, _, name)| !keep.contains(name));
+    found
+}
+
+// A declaration's comma-separated declarators after its keyword, each a name or a
+// destructuring pattern with an optional type and initializer: `a = 1, { b } = o, [c]: T[] = []`
+fn declarators<'a>(tokens: &[Token<'a>], kind: BindingKind, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    let mut start = 0;
+    while let Some(first) = tokens.get(start).filter(|first| starts_pattern(first)) {
+        let end = if first.opens() { (lexer::matching(tokens, start) + 1).min(tokens.len()) } else { start + 1 };
+        pattern_bindings(&tokens[start..end], kind, found);
+        match declarator_end(tokens, end) {
+            Some(comma) => start = comma + 1,
+            None => return,
+        }
+    }
+}
+
+// Index of the comma after the type and initializer starting at start, or None when the
+// declaration ends first: at `;`, `in` or `of`, a bracket closing around it, or a line
+// break between two statements
+fn declarator_end(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    // Inside the type annotation, where `<` and `>` nest too
+    let mut angles: Option<usize> = None;
+    let mut initialized = false;
+    for i in start..tokens.len() {
+        let token = &tokens[i];
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.checked_sub(1)?;
+        } else if depth > 0 {
+            continue;
+        } else if i > start && token.line > tokens[i - 1].line && ends_statement(&tokens[i - 1]) && starts_statement(token) {
+            return None;
+        } else if token.is(",") && angles.is_none_or(|angle| angle == 0) {
+            return Some(i);
+        } else if token.is(";") || (!initialized && (token.is("in") || token.is("of"))) {
+            return None;
+        } else if token.is("=") {
+            initialized = true;
+            angles = None;
+        } else if token.is(":") && !initialized {
+            angles = Some(0);
+        } else if let Some(angle) = angles.as_mut() {
+            if token.is("<") {
+                *angle += 1;
+            } else if token.is(">") {
+                *angle = angle.saturating_sub(1);
+            }
+        }
+    }
+    None
+}
+
+// A line ending in one of these may end its statement
+fn ends_statement(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Ident | TokenKind::Number | TokenKind::Str) || token.closes()
+}
+
+// A line starting with one of these after one that may end starts a new statement
+fn starts_statement(token: &Token) -> bool {
+    matches!(token.kind, TokenKind::Ident | TokenKind::Number | TokenKind::Str | TokenKind::Markup)
+}
+
+fn starts_pattern(token: &Token) -> bool {
+    token.is("{") || token.is("[") || (token.kind == TokenKind::Ident && !lexer::is_keyword(JAVASCRIPT, token.text))
+}
+
+// The names a pattern binds: `x`, `{ a, b: c, d = 1, ...rest }`, `[x, , y = 1]`,
+// nested to any depth. Object keys and default values aren't bindings.
+fn pattern_bindings<'a>(tokens: &[Token<'a>], kind: BindingKind, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    let Some(first) = tokens.first() else {
+        return;
+    };
+    if first.is("...") {
+        pattern_bindings(&tokens[1..], kind, found);
+    } else if first.is("{") || first.is("[") {
+        let close = lexer::matching(tokens, 0);
+        for element in lexer::split(&tokens[1..close]) {
+            // An object pattern's entries bind their value, after the key
+            let value = match lexer::top_level(element, |token| token.is(":")) {
+                Some(colon) if first.is("{") => &element[colon + 1..],
+                _ => element,
+            };
+            let default = lexer::top_level(value, |token| token.is("=")).unwrap_or(value.len());
+            pattern_bindings(&value[..default], kind, found);
+        }
+    } else if first.kind == TokenKind::Ident && !lexer::is_keyword(JAVASCRIPT, first.text) {
+        found.push((first.offset, kind, first.text));
+    }
+}
+
+// A parameter's pattern without its decorators, modifiers, `?`, type and default, and
+// whether a modifier makes it a parameter property
+fn parameter_pattern<'a, 'b>(parameter: &'b [Token<'a>]) -> (bool, &'b [Token<'a>]) {
+    let mut start = 0;
+    let mut property = false;
+    while let Some(token) = parameter.get(start) {
+        if token.is("@") {
+            // A decorator: `@Inject(TOKEN)`
+            start += 1;
+            while parameter.get(start).is_some_and(|token| token.kind == TokenKind::Ident || token.is(".")) {
+                start += 1;
+            }
+            if parameter.get(start).is_some_and(|token| token.is("(")) {
+                start = lexer::matching(parameter, start) + 1;
+            }
+        } else if PARAMETER_PROPERTIES.iter().any(|modifier| token.is(modifier))
+            && parameter.get(start + 1).is_some_and(starts_pattern)
+        {
+            property = true;
+            start += 1;
+        } else {
+            break;
+        }
+    }
+    let pattern = &parameter[start.min(parameter.len())..];
+    let end = lexer::top_level(pattern, |token| token.is(":") || token.is("=") || token.is("?")).unwrap_or(pattern.len());
+    (property, &pattern[..end])
+}
+
+// What kind of parameters the parentheses from open to close hold, if they're a
+// parameter list: an arrow function's, or a function's or method's followed by its body
+fn parameter_list(tokens: &[Token], open: usize, close: usize) -> Option<BindingKind> {
+    let mut after = close + 1;
+    if tokens.get(after).is_some_and(|token| token.is(":")) {
+        // A return type
+        after = type_end(tokens, after + 1);
+    }
+    let next = tokens.get(after)?;
+    if next.is("=>") {
+        Some(BindingKind::LambdaParam)
+    } else if next.is("{") && names_function(tokens, open) {
+        Some(BindingKind::Parameter)
+    } else {
+        None
+    }
+}
+
+// Index of the token ending the type annotation starting at start: the first `=>`,
+// `{` (unless it opens an object type), `;`, `,`, `=` or closing bracket outside the
+// type's own brackets and generics
+fn type_end(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut angles = 0usize;
+    for i in start..tokens.len() {
+        let token = &tokens[i];
+        let object_type = token.is("{") && (i == start || tokens[i - 1].is("|") || tokens[i - 1].is("&"));
+        let ends = ["=>", ";", ",", "="].iter().any(|end| token.is(end)) || (token.is("{") && !object_type) || token.closes();
+        if depth == 0 && ((angles == 0 && ends) || token.closes()) {
+            return i;
+        }
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth -= 1;
+        } else if depth == 0 && token.is("<") {
+            angles += 1;
+        } else if depth == 0 && token.is(">") {
+            angles = angles.saturating_sub(1);
+        }
+    }
+    tokens.len()
+}
+
+// Whether the `(` at open follows `function`, a generator's `*`, or a function or
+// method name, with or without generics: `function f<T>(`, `async load(`
+fn names_function(tokens: &[Token], open: usize) -> bool {
+    let mut j = open;
+    if j > 0 && tokens[j - 1].is(">") {
+        // Back to the `<` opening the generics
+        let mut depth = 0usize;
+        while j > 0 {
+            j -= 1;
+            if tokens[j].is(">") {
+                depth += 1;
+            } else if tokens[j].is("<") {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+    }
+    let Some(previous) = j.checked_sub(1).map(|k| &tokens[k]) else {
+        return false;
+    };
+    previous.is("function") || previous.is("*") || (previous.kind == TokenKind::Ident && !lexer::is_keyword(JAVASCRIPT, previous.text))
+}
+
+// `.` and `?.` come before a property, `#` before a private field's name
+fn is_member_access(token: &Token) -> bool {
+    token.is(".") || token.is("?.") || token.is("#")
+}
+
+// content's tokens without comments and line breaks
+fn code_tokens(content: &str) -> Vec<Token<'_>> {
+    lexer::tokenize(content, JAVASCRIPT)
+        .into_iter()
+        .filter(|token| !matches!(token.kind, TokenKind::Comment | TokenKind::Newline))
+        .collect()
+}
+
+// What an open bracket is
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    // An object literal, destructuring pattern or object type, whose keys aren't variables
+    Object,
+    // A class, interface or enum body, whose member names aren't variables
+    Members,
+    // `import { a, b as c }`, where a name is the module's as well as the file's
+    Import,
+    // `export { a, b as c }`, likewise
+    Export,
+    // `export { a } from "./b"`, where no name is the file's
+    Reexport,
+    Other,
+}
+
+// Renames every use of each old name in code and in template literals' `${...}`, and
+// in JSX expressions, leaving alone object keys, class and interface members,
+// properties (`obj.x`), JSX tags and attributes, comments and strings. Shorthand
+// properties keep their key (`{ x }` becomes `{ x: renamed }`), and names imported or
+// exported in braces keep the module's name for them (`{ renamed as x }`).
+pub(crate) fn rename(content: &str, replacements: &[(String, String)]) -> String {
+    let names: HashMap<&str, &str> = replacements.iter().map(|(old, new)| (old.as_str(), new.as_str())).collect();
+    let tokens = code_tokens(content);
+    let mut edits = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+    // The depth at which each `class`, `interface` or `enum` being read opens its body
+    let mut bodies: Vec<usize> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        let next = tokens.get(i + 1);
+        match token.kind {
+            TokenKind::Ident if matches!(token.text, "class" | "interface" | "enum") && !previous.is_some_and(is_member_access) => {
+                bodies.push(brackets.len());
+            }
+            TokenKind::Punct if token.opens() => {
+                let bracket = if !token.is("{") {
+                    Bracket::Other
+                } else if bodies.last() == Some(&brackets.len()) {
+                    bodies.pop();
+                    Bracket::Members
+                } else if is_import(&tokens, i) {
+                    Bracket::Import
+                } else if previous.is_some_and(|previous| previous.is("export")) || (i >= 2 && tokens[i - 1].is("type") && tokens[i - 2].is("export")) {
+                    let from = tokens.get(lexer::matching(&tokens, i) + 1).is_some_and(|after| after.is("from"));
+                    if from {
+                        Bracket::Reexport
+                    } else {
+                        Bracket::Export
+                    }
+                } else if opens_object(&tokens, i) {
+                    Bracket::Object
+                } else {
+                    Bracket::Other
+                };
+                brackets.push(bracket);
+            }
+            TokenKind::Punct if token.closes() => {
+                brackets.pop();
+                bodies.retain(|depth| *depth <= brackets.len());
+            }
+            TokenKind::Ident => {
+                let Some(new_name) = names.get(token.text) else {
+                    continue;
+                };
+                if previous.is_some_and(is_member_access) {
+                    continue;
+                }
+                let entry_start = previous.is_some_and(|previous| previous.is("{") || previous.is(","));
+                let before_as = next.is_some_and(|next| next.is("as"));
+                let after_as = previous.is_some_and(|previous| previous.is("as"));
+                match brackets.last() {
+                    Some(Bracket::Reexport) => continue,
+                    Some(Bracket::Import) if before_as => continue,
+                    Some(Bracket::Import) if !after_as => {
+                        // The module's name stays, the file's becomes an alias
+                        edits.push((token.offset + token.text.len(), 0, format!(" as {}", new_name)));
+                        continue;
+                    }
+                    Some(Bracket::Export) if after_as => continue,
+                    Some(Bracket::Export) if !before_as => {
+                        edits.push((token.offset, token.text.len(), format!("{} as {}", new_name, token.text)));
+                        continue;
+                    }
+                    Some(Bracket::Members) if is_member_start(&tokens, i) => continue,
+                    Some(Bracket::Object) => {
+                        if is_key(&tokens, i) {
+                            continue;
+                        }
+                        if entry_start && next.is_some_and(|next| next.is(",") || next.is("}") || next.is("=")) {
+                            // Shorthand: the key stays and the value is the variable by its new name
+                            edits.push((token.offset, 0, format!("{}: ", token.text)));
+                        }
+                    }
+                    _ => {}
+                }
+                edits.push((token.offset, token.text.len(), new_name.to_string()));
+            }
+            _ => {}
+        }
+    }
+    lexer::apply_edits(content, edits)
+}
+
+// Whether the `{` at open is the one in `import {`, `import type {` or `import React, {`
+fn is_import(tokens: &[Token], open: usize) -> bool {
+    let mut j = open;
+    if j >= 2 && tokens[j - 1].is(",") && tokens[j - 2].kind == TokenKind::Ident {
+        j -= 2;
+    }
+    if j >= 1 && tokens[j - 1].is("type") {
+        j -= 1;
+    }
+    j >= 1 && tokens[j - 1].is("import")
+}
+
+// Whether the `{` at open starts an object literal, destructuring pattern or object
+// type rather than a block: it does after most operators and a few keywords, but not
+// after `)`, `=>`, a `case` label or a name, as in `if (x) {`, `() => {`, `class A {`
+fn opens_object(tokens: &[Token], open: usize) -> bool {
+    let Some(previous) = open.checked_sub(1).map(|j| &tokens[j]) else {
+        return false;
+    };
+    match previous.kind {
+        TokenKind::Punct if previous.is("{") => {
+            // A JSX attribute's object, `style={{ color }}`
+            open >= 2 && tokens[open - 2].kind == TokenKind::Markup
+        }
+        TokenKind::Punct if previous.is(":") => !ends_case(tokens, open - 1),
+        TokenKind::Punct => !matches!(previous.text, ")" | "]" | "}" | ";" | "=>" | ">"),
+        TokenKind::Ident => BEFORE_OBJECT.contains(&previous.text),
+        _ => false,
+    }
+}
+
+// Whether the `:` at colon ends a `case` or `default` label
+fn ends_case(tokens: &[Token], colon: usize) -> bool {
+    let mut j = colon;
+    while j > 0 {
+        let token = &tokens[j - 1];
+        if token.is("case") || token.is("default") {
+            return true;
+        }
+        if token.closes() {
+            j = lexer::opening(tokens, j - 1);
+        } else if token.opens() || ["?", ",", ";", ":"].iter().any(|stop| token.is(stop)) {
+            return false;
+        } else {
+            j -= 1;
+        }
+    }
+    false
+}
+
+// Whether the name at i is an object's key or method name rather than a value:
+// `{ key: value }`, `{ method() {} }`, `{ get size() {} }`, or an object type's `{ key?: T }`
+fn is_key(tokens: &[Token], i: usize) -> bool {
+    let next = tokens.get(i + 1);
+    let keyed = next.is_some_and(|next| next.is(":") || next.is("(") || next.is("<"))
+        || (next.is_some_and(|next| next.is("?")) && tokens.get(i + 2).is_some_and(|after| after.is(":")));
+    let previous = &tokens[i - 1];
+    let starts = ["{", ",", ";", "*"].iter().any(|before| previous.is(before))
+        || MODIFIERS.iter().any(|modifier| previous.is(modifier))
+        || previous.line < tokens[i].line;
+    keyed && starts
+}
+
+// Whether the name at i starts a class, interface or enum member, so it's the member's name
+fn is_member_start(tokens: &[Token], i: usize) -> bool {
+    let previous = &tokens[i - 1];
+    ["{", ";", "}", ",", ")", "*"].iter().any(|before| previous.is(before))
+        || MODIFIERS.iter().any(|modifier| previous.is(modifier))
+        || (previous.line < tokens[i].line && ends_statement(previous))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bound(content: &str) -> Vec<(usize, BindingKind, &str)> {
+        let mut found = bindings(content);
+        found.sort_by_key(|(offset, _, _)| *offset);
+        found
+    }
+
+    fn renamed(content: &str, old: &str, new: &str) -> String {
+        rename(content, &[(old.to_string(), new.to_string())])
+    }
+
+    #[test]
+    fn destructuring_loops_and_parameters() {
+        let content = "const { a: b = 1, ...rest } = o, [c, , d] = xs;\nfor (const [k, v] of m) {}\nfunction f(p: T, { q }, ...r) {}\nconst g = (s) => s;\ntry {} catch (e) {}\n";
+        let names: Vec<(BindingKind, &str)> = bound(content).into_iter().map(|(_, kind, name)| (kind, name)).collect();
+        assert_eq!(
+            names,
+            [
+                (BindingKind::Variable, "b"),
+                (BindingKind::Variable, "rest"),
+                (BindingKind::Variable, "c"),
+                (BindingKind::Variable, "d"),
+                (BindingKind::LoopIndex, "k"),
+                (BindingKind::LoopIndex, "v"),
+                (BindingKind::Parameter, "p"),
+                (BindingKind::Parameter, "q"),
+                (BindingKind::Parameter, "r"),
+                (BindingKind::Variable, "g"),
+                (BindingKind::LambdaParam, "s"),
+                (BindingKind::ExceptionBinding, "e"),
+            ]
+        );
+        let (offset, _, _) = bound(content)[0];
+        assert_eq!(&content[offset..offset + 1], "b");
+    }
+
+    #[test]
+    fn exported_names_and_parameter_properties_are_kept() {
+        let content = "export const tmp = 1;\nclass A { constructor(private x: number, y: number) {} }\n";
+        let names: Vec<&str> = bound(content).into_iter().map(|(_, _, name)| name).collect();
+        assert_eq!(names, ["y"]);
+    }
+
+    #[test]
+    fn object_shorthand_keeps_its_key() {
+        let content = "const x = 1;\nconst o = { x, y: x };\nconst { x: z } = o;\n";
+        assert_eq!(renamed(content, "x", "width"), "const width = 1;\nconst o = { x: width, y: width };\nconst { x: z } = o;\n");
+    }
+
+    #[test]
+    fn members_after_dot_and_optional_chaining_are_left_alone() {
+        let content = "let x = o.x ?? o?.x;\nclass C { x = 1; m() { return this.x + x; } }\n";
+        assert_eq!(renamed(content, "x", "n"), "let n = o.x ?? o?.x;\nclass C { x = 1; m() { return this.x + n; } }\n");
+    }
+
+    #[test]
+    fn template_literals_rename_only_their_expressions() {
+        let content = "const x = 1;\nlog(`x = ${x + `${x}`} x`, 'x');\n";
+        assert_eq!(renamed(content, "x", "n"), "const n = 1;\nlog(`x = ${n + `${n}`} x`, 'x');\n");
+    }
+
+    #[test]
+    fn regex_literals_are_not_division() {
+        let content = "let x = 4;\nconst r = /x/g, half = x / 2 / x;\n";
+        assert_eq!(renamed(content, "x", "n"), "let n = 4;\nconst r = /x/g, half = n / 2 / n;\n");
+    }
+
+    #[test]
+    fn imports_keep_the_module_name() {
+        let content = "import { tmp } from './m';\nexport { tmp };\nuse(tmp);\n";
+        assert_eq!(renamed(content, "tmp", "t"), "import { tmp as t } from './m';\nexport { t as tmp };\nuse(t);\n");
+    }
+
+    #[test]
+    fn every_prefix_of_a_file_being_typed_is_handled() {
+        let content = "import React, { useState as use } from 'react';\nexport const tmp = 1;\ntype P = { a: number; b?: string };\nclass A<T> extends B {\n  private readonly x: Map<string, T[]> = new Map();\n  constructor(private y: number, z = 1) { super(); }\n  async *m<U>({ a: b = 1, ...rest }: P, [c, , d] = [], ...e: U[]) {\n    for await (const [k, v] of this.x) { if (k / 2 > /x/g.lastIndex) break; }\n    try { let { f } = o, g = `${b + `${c}`}`; } catch ({ message }) {}\n    const h = (i) => i?.j ?? (() => ({ k }))();\n    return <div className={tmp}>{d} text</div>;\n  }\n}\n";
+        let replacements = [("tmp".to_string(), "total".to_string()), ("c".to_string(), "count".to_string())];
+        for end in (0..=content.len()).filter(|&end| content.is_char_boundary(end)) {
+            bindings(&content[..end]);
+            rename(&content[..end], &replacements);
+        }
+    }
+}
+
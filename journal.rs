@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const JOURNAL_DIR: &str = ".badvars-journal";
+
+// The original contents of every file a run writes, kept on disk until the run has
+// been verified. The manifest lists, one per line, `<n>\t<path>` for a file whose
+// original is saved as `<n>.orig`, or `created\t<path>` for a file the run created.
+pub struct Journal {
+    dir: PathBuf,
+    entries: Vec<(PathBuf, Option<PathBuf>)>,
+    seen: HashSet<PathBuf>,
+}
+
+impl Journal {
+    pub fn begin() -> io::Result<Self> {
+        let dir = PathBuf::from(JOURNAL_DIR);
+        if dir.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} is left over from an interrupted run; its manifest lists the original of each file it touched. Restore what you need and delete it",
+                    JOURNAL_DIR
+                ),
+            ));
+        }
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(".gitignore"), "*\n")?;
+        Ok(Journal { dir, entries: Vec::new(), seen: HashSet::new() })
+    }
+
+    // Call before writing file_path; only the first call for a path saves anything
+    pub fn record(&mut self, file_path: &Path) -> io::Result<()> {
+        if !self.seen.insert(file_path.to_path_buf()) {
+            return Ok(());
+        }
+        let saved = if file_path.exists() {
+            let saved = self.dir.join(format!("{}.orig", self.entries.len()));
+            fs::copy(file_path, &saved)?;
+            Some(saved)
+        } else {
+            None
+        };
+
+        // Appended as we go, so the manifest is complete even if the run dies halfway
+        let mut manifest = OpenOptions::new().create(true).append(true).open(self.dir.join("manifest"))?;
+        match &saved {
+            Some(_) => writeln!(manifest, "{}\t{}", self.entries.len(), file_path.display())?,
+            None => writeln!(manifest, "created\t{}", file_path.display())?,
+        }
+        self.entries.push((file_path.to_path_buf(), saved));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Puts every recorded file back the way it was, deletes the ones the run created and
+    // removes the journal; returns how many files were restored
+    pub fn rollback(self) -> io::Result<usize> {
+        let mut restored = 0;
+        for (file_path, saved) in self.entries.iter().rev() {
+            match saved {
+                Some(saved) => {
+                    fs::copy(saved, file_path)?;
+                    restored += 1;
+                }
+                None => {
+                    if file_path.exists() {
+                        fs::remove_file(file_path)?;
+                    }
+                }
+            }
+        }
+        fs::remove_dir_all(&self.dir)?;
+        Ok(restored)
+    }
+
+    // Keeps the changes and removes the journal
+    pub fn commit(self) -> io::Result<()> {
+        fs::remove_dir_all(&self.dir)
+    }
+}
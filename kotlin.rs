@@ -0,0 +1,364 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::language_patterns::{BindingKind, LanguagePattern};
+use crate::lexer::{self, Token, TokenKind};
+
+const KOTLIN: &LanguagePattern = &LanguagePattern::Kotlin;
+
+// Words before a parameter's name: `vararg xs: Int`, `private val x: Int`
+const MODIFIERS: &[&str] = &[
+    "vararg", "noinline", "crossinline", "val", "var", "private", "protected", "public", "internal", "override", "open",
+    "final",
+];
+
+// Words that may come between `class` and its body: `data class Foo<T>(val x: T) : Bar(), Baz where T : Any {`
+const HEADER_WORDS: &[&str] = &["class", "object", "interface", "where", "by", "constructor", "in", "out"];
+
+// What an open bracket is
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    // A class, object or interface body
+    Members,
+    // The parameters of a function, constructor or class
+    Parameters,
+    Other,
+}
+
+// Every name bound in content: `val` and `var` locals including destructured ones,
+// parameters of functions, constructors and lambdas (`{ a, (b, c) -> }`), `for`
+// variables, `catch` variables and `when (val x = ...)`. Properties, including
+// constructor ones and top-level ones, are reached by name from other files, so
+// nothing is reported for a name declared as one; likewise for a function's name, a
+// name the file passes as a named argument (`f(x = 1)`) or a label.
+pub(crate) fn bindings(content: &str) -> Vec<(usize, BindingKind, &str)> {
+    let tokens = code_tokens(content);
+    let mut keep: HashSet<&str> = named_arguments(&tokens).into_iter().chain(labels(&tokens)).map(|i| tokens[i].text).collect();
+    let mut found = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        if token.is("val") || token.is("var") {
+            let names = declared(&tokens, i + 1);
+            match brackets.last() {
+                None | Some(Bracket::Members) => keep.extend(names.iter().map(|name| name.text)),
+                Some(Bracket::Parameters) => {}
+                Some(Bracket::Other) => found.extend(names.iter().map(|name| (name.offset, BindingKind::Variable, name.text))),
+            }
+        } else if token.is("fun") {
+            // Its name: `fun foo(`, `fun <T> List<T>.foo(`
+            let name = tokens[i + 1..].iter().take_while(|token| !token.is("(")).last().filter(|name| is_name(name));
+            keep.extend(name.map(|name| name.text));
+        } else if token.is("(") {
+            let close = lexer::matching(&tokens, i);
+            if previous.is_some_and(|previous| previous.is("for")) {
+                // `for (x in xs)`, `for ((k, v) in map)`
+                let end = lexer::top_level(&tokens[i + 1..close], |token| token.is("in")).map_or(close, |end| i + 1 + end);
+                let names = declared(&tokens[..end], i + 1);
+                found.extend(names.iter().map(|name| (name.offset, BindingKind::LoopIndex, name.text)));
+            } else if previous.is_some_and(|previous| previous.is("catch")) {
+                let name = tokens.get(i + 1).filter(|name| is_name(name));
+                found.extend(name.map(|name| (name.offset, BindingKind::ExceptionBinding, name.text)));
+            } else if is_parameter_list(&tokens, i) {
+                for parameter in lexer::split_parameters(&tokens[i + 1..close]) {
+                    let start = parameter.iter().take_while(|token| MODIFIERS.iter().any(|modifier| token.is(modifier))).count();
+                    let Some(name) = parameter.get(start).filter(|name| is_name(name)) else {
+                        continue;
+                    };
+                    if parameter[..start].iter().any(|token| token.is("val") || token.is("var")) {
+                        // A constructor property
+                        keep.insert(name.text);
+                    } else {
+                        found.push((name.offset, BindingKind::Parameter, name.text));
+                    }
+                }
+            }
+        } else if token.is("{") && !follows_when(&tokens, i) {
+            lambda_parameters(&tokens, i, &mut found);
+        }
+
+        if token.opens() {
+            brackets.push(bracket(&tokens, i));
+        } else if token.closes() {
+            brackets.pop();
+        }
+    }
+    found.retain(|(_, _, name)| !keep.contains(name));
+    found
+}
+
+// The names a declaration binds, starting at the name or the `(` of a destructuring:
+// `x: Int = 1`, `(a, _, c: Int) = triple`. An extension property's `Foo.bar` binds
+// nothing.
+fn declared<'a, 'b>(tokens: &'b [Token<'a>], start: usize) -> Vec<&'b Token<'a>> {
+    match tokens.get(start) {
+        Some(token) if token.is("(") => {
+            let close = lexer::matching(tokens, start);
+            lexer::split_parameters(&tokens[start + 1..close]).into_iter().filter_map(|part| part.first()).filter(|name| is_name(name)).collect()
+        }
+        Some(name) if is_name(name) && !tokens.get(start + 1).is_some_and(|next| next.is(".")) => vec![name],
+        _ => Vec::new(),
+    }
+}
+
+// A lambda's parameters, when the `{` at open starts one: `{ x -> }`, `{ a, b: Int -> }`,
+// `{ (k, v) -> }`
+fn lambda_parameters<'a>(tokens: &[Token<'a>], open: usize, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    let close = lexer::matching(tokens, open);
+    let Some(arrow) = lexer::top_level(&tokens[open + 1..close], |token| !is_parameter_part(token)).map(|end| open + 1 + end) else {
+        return;
+    };
+    if !tokens[arrow].is("->") {
+        return;
+    }
+    for parameter in lexer::split_parameters(&tokens[open + 1..arrow]) {
+        for name in declared(parameter, 0) {
+            found.push((name.offset, BindingKind::LambdaParam, name.text));
+        }
+    }
+}
+
+// Whether token may be part of a lambda's parameters before its `->`
+fn is_parameter_part(token: &Token) -> bool {
+    token.kind == TokenKind::Ident || [",", ":", ".", "?", "<", ">", "*"].iter().any(|part| token.is(part))
+}
+
+// Whether the `{` at open starts a `when`'s branches, which aren't a lambda: `when {`, `when (x) {`
+fn follows_when(tokens: &[Token], open: usize) -> bool {
+    match open.checked_sub(1).map(|j| &tokens[j]) {
+        Some(previous) if previous.is(")") => {
+            let paren = lexer::opening(tokens, open - 1);
+            paren > 0 && tokens[paren - 1].is("when")
+        }
+        Some(previous) => previous.is("when"),
+        None => false,
+    }
+}
+
+// Whether the `(` at open starts the parameters of a function, constructor or class:
+// `fun foo(`, `fun <T> T.foo(`, `fun(`, `constructor(`, `class Foo<T>(`
+fn is_parameter_list(tokens: &[Token], open: usize) -> bool {
+    let mut j = open;
+    while j > 0 {
+        let token = &tokens[j - 1];
+        if token.is("fun") || token.is("constructor") || token.is("class") {
+            return true;
+        }
+        if token.is(">") {
+            // Back over type parameters or a receiver's type arguments
+            j = angle_opening(tokens, j - 1);
+        } else if is_name(token) || token.is(".") || token.is("?") {
+            j -= 1;
+        } else {
+            return false;
+        }
+    }
+    false
+}
+
+fn is_name(token: &Token) -> bool {
+    token.kind == TokenKind::Ident && !lexer::is_keyword(KOTLIN, token.text) && token.text != "_"
+}
+
+// What the bracket at open is
+fn bracket(tokens: &[Token], open: usize) -> Bracket {
+    let token = &tokens[open];
+    if token.is("(") && is_parameter_list(tokens, open) {
+        return Bracket::Parameters;
+    }
+    if token.is("{") && is_type_body(tokens, open) {
+        return Bracket::Members;
+    }
+    Bracket::Other
+}
+
+// Whether the `{` at open starts the body of a class, object or interface, including
+// `object : Runnable {` and `companion object {`. Only a declaration's header may come
+// between the keyword and the body.
+fn is_type_body(tokens: &[Token], open: usize) -> bool {
+    let mut j = open;
+    while j > 0 {
+        let token = &tokens[j - 1];
+        if ["class", "object", "interface"].iter().any(|keyword| token.is(keyword)) {
+            return j < 2 || !tokens[j - 2].is("::");
+        }
+        let header = token.kind == TokenKind::Ident && (HEADER_WORDS.contains(&token.text) || !lexer::is_keyword(KOTLIN, token.text));
+        if token.is(")") || token.is(">") {
+            j = if token.is(")") { lexer::opening(tokens, j - 1) } else { angle_opening(tokens, j - 1) };
+        } else if header || [":", ",", ".", "?", "*"].iter().any(|part| token.is(part)) {
+            j -= 1;
+        } else {
+            return false;
+        }
+    }
+    false
+}
+
+// Index of the `<` opening the type arguments closed at close
+fn angle_opening(tokens: &[Token], close: usize) -> usize {
+    let mut depth = 0usize;
+    for i in (0..=close).rev() {
+        if tokens[i].is(">") {
+            depth += 1;
+        } else if tokens[i].is("<") {
+            depth -= 1;
+            if depth == 0 {
+                return i;
+            }
+        }
+    }
+    0
+}
+
+// Indexes of the names of the arguments the file passes by name: the x in `f(x = 1)`
+fn named_arguments(tokens: &[Token]) -> Vec<usize> {
+    (1..tokens.len().saturating_sub(1))
+        .filter(|&i| tokens[i].kind == TokenKind::Ident && (tokens[i - 1].is("(") || tokens[i - 1].is(",")) && tokens[i + 1].is("="))
+        .collect()
+}
+
+// Indexes of label names: `outer@ for`, `break@outer`, `return@forEach`, `this@Outer`
+fn labels(tokens: &[Token]) -> Vec<usize> {
+    let mut found = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is("@") && i > 0 && adjacent(&tokens[i - 1], token) {
+            found.push(i - 1);
+            if tokens.get(i + 1).is_some_and(|next| next.kind == TokenKind::Ident && adjacent(token, next)) {
+                found.push(i + 1);
+            }
+        }
+    }
+    found
+}
+
+// Whether b starts right where a ends
+fn adjacent(a: &Token, b: &Token) -> bool {
+    (a.kind == TokenKind::Ident && a.offset + a.text.len() == b.offset) || (a.is("@") && b.offset == a.offset + 1)
+}
+
+// content's tokens without comments, line breaks and annotations
+fn code_tokens(content: &str) -> Vec<Token<'_>> {
+    let tokens: Vec<Token> = lexer::tokenize(content, KOTLIN)
+        .into_iter()
+        .filter(|token| !matches!(token.kind, TokenKind::Comment | TokenKind::Newline))
+        .collect();
+    let mut code: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        let label = i > 0 && adjacent(&tokens[i - 1], &token);
+        if !token.is("@") || label {
+            code.push(token);
+            i += 1;
+            continue;
+        }
+        // An annotation: `@Inject`, `@file:JvmName("x")`, `@field:Json(name = "x")`, `@[A B]`
+        i += 1;
+        if tokens.get(i + 1).is_some_and(|next| next.is(":")) {
+            i += 2;
+        }
+        if tokens.get(i).is_some_and(|token| token.is("[")) {
+            i = lexer::matching(&tokens, i) + 1;
+            continue;
+        }
+        i += 1;
+        while tokens.get(i).is_some_and(|token| token.is(".")) && tokens.get(i + 1).is_some_and(|next| next.kind == TokenKind::Ident) {
+            i += 2;
+        }
+        if tokens.get(i).is_some_and(|token| token.is("(") && token.offset == tokens[i - 1].offset + tokens[i - 1].text.len()) {
+            i = lexer::matching(&tokens, i) + 1;
+        }
+    }
+    code
+}
+
+// Renames every use of each old name in code and in string templates, leaving alone
+// members reached through `.`, `?.` or `::`, named arguments (`f(x = 1)`), labels,
+// comments and strings
+pub(crate) fn rename(content: &str, replacements: &[(String, String)]) -> String {
+    let names: HashMap<&str, &str> = replacements.iter().map(|(old, new)| (old.as_str(), new.as_str())).collect();
+    let tokens = code_tokens(content);
+    let skipped: HashSet<usize> = named_arguments(&tokens).into_iter().chain(labels(&tokens)).collect();
+    let mut edits = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Ident || skipped.contains(&i) {
+            continue;
+        }
+        let Some(new_name) = names.get(token.text) else {
+            continue;
+        };
+        let member = i > 0 && [".", "?.", "::"].iter().any(|access| tokens[i - 1].is(access));
+        if !member {
+            edits.push((token.offset, token.text.len(), new_name.to_string()));
+        }
+    }
+    lexer::apply_edits(content, edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bound(content: &str) -> Vec<(BindingKind, &str)> {
+        let mut found = bindings(content);
+        found.sort_by_key(|(offset, _, _)| *offset);
+        found.into_iter().map(|(_, kind, name)| (kind, name)).collect()
+    }
+
+    fn renamed(content: &str, old: &str, new: &str) -> String {
+        rename(content, &[(old.to_string(), new.to_string())])
+    }
+
+    #[test]
+    fn properties_are_members_and_locals_are_bound() {
+        let content = "val top = 1\nclass A(val p: Int, q: Int) {\n    var tmp = 0\n    fun run(n: Int) {\n        val (a, _, c) = t\n        for ((k, v) in m) {}\n        xs.forEach { x -> }\n        try {} catch (e: Exception) {}\n        when (val w = f()) { else -> {} }\n    }\n}\n";
+        assert_eq!(
+            bound(content),
+            [
+                (BindingKind::Parameter, "q"),
+                (BindingKind::Parameter, "n"),
+                (BindingKind::Variable, "a"),
+                (BindingKind::Variable, "c"),
+                (BindingKind::LoopIndex, "k"),
+                (BindingKind::LoopIndex, "v"),
+                (BindingKind::LambdaParam, "x"),
+                (BindingKind::ExceptionBinding, "e"),
+                (BindingKind::Variable, "w"),
+            ]
+        );
+        assert_eq!(bindings(content).iter().map(|(offset, _, _)| *offset).min(), content.find("q:"));
+    }
+
+    #[test]
+    fn named_arguments_and_labels_are_kept() {
+        let content = "fun f(count: Int, step: Int) = count * step\nfun g(n: Int) { f(count = 1, step = n); outer@ for (i in xs) { break@outer } }\n";
+        assert_eq!(bound(content), [(BindingKind::Parameter, "n"), (BindingKind::LoopIndex, "i")]);
+        assert_eq!(
+            renamed("fun g(count: Int) = f(count = count)\n", "count", "n"),
+            "fun g(n: Int) = f(count = n)\n"
+        );
+    }
+
+    #[test]
+    fn string_templates_and_raw_strings() {
+        let content = "fun f(x: Int) = \"$x ${x + 1} \\$x x\" + \"\"\"$x x\"\"\"\n";
+        assert_eq!(renamed(content, "x", "n"), "fun f(n: Int) = \"$n ${n + 1} \\$x x\" + \"\"\"$n x\"\"\"\n");
+    }
+
+    #[test]
+    fn members_and_annotations_are_left_alone() {
+        let content = "@Json(name = \"x\") fun f(x: Int) = this.x + o?.x + ::x.name + x\n";
+        assert_eq!(renamed(content, "x", "n"), "@Json(name = \"x\") fun f(n: Int) = this.x + o?.x + ::x.name + n\n");
+    }
+
+    #[test]
+    fn every_prefix_of_a_file_being_typed_is_handled() {
+        let content = "@file:JvmName(\"M\")\npackage m\n\ndata class P<T>(val x: T, private var y: Int = 0) : Base(), I where T : Any {\n    companion object { const val TMP = 1 }\n    fun <U> List<U>.f(vararg items: U, g: (U) -> Unit = {}): Int {\n        val (a, _, c) = triple\n        for ((k, v) in map) { println(\"$k ${v + a} \\$x\") }\n        for (i in list) { outer@ while (true) { break@outer } }\n        items.forEach { item -> g(item) }\n        try { f(x = 1) } catch (e: Exception) {}\n        return when (val w = c) { is Int -> w; else -> \"\"\"raw $a\"\"\".length }\n    }\n}\n";
+        let replacements = [("a".to_string(), "alpha".to_string()), ("x".to_string(), "width".to_string())];
+        for end in (0..=content.len()).filter(|&end| content.is_char_boundary(end)) {
+            bindings(&content[..end]);
+            rename(&content[..end], &replacements);
+        }
+    }
+}
+
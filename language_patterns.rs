@@ -1,6 +1,8 @@
 use regex::Regex;
 
-#[derive(Debug, Clone)]
+use crate::{java, javascript, kotlin, python, rust};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LanguagePattern {
     Rust,
     JavaScript,
@@ -18,150 +20,150 @@ pub enum LanguagePattern {
     Scala,
 }
 
+// What kind of binding a pattern captures, so rules can treat `i` in a loop
+// header differently from `i` declared at function scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    Variable,
+    Parameter,
+    LoopIndex,
+    LambdaParam,
+    Comprehension,
+    ExceptionBinding,
+    PatternBinding,
+}
+
 impl LanguagePattern {
-    pub fn get_variable_patterns(&self) -> Vec<Regex> {
+    // Every name content binds, as (offset, kind, name) in no particular order. Python,
+    // Rust, JavaScript, Java, C# and Kotlin have dedicated extractors; the other
+    // languages run their get_variable_patterns.
+    pub fn find_bindings<'a>(&self, content: &'a str) -> Vec<(usize, BindingKind, &'a str)> {
         match self {
-            LanguagePattern::Rust => vec![
-                // let var_name =, let mut var_name =
-                Regex::new(r"\blet\s+(?:mut\s+)?([a-zA-Z_][a-zA-Z0-9_]*)\s*[=:]").unwrap(),
-                // for var_name in
-                Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+in\b").unwrap(),
-                // function parameters: fn name(var_name: type)
-                Regex::new(r"\bfn\s+[a-zA-Z_][a-zA-Z0-9_]*\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*:").unwrap(),
-                // closure parameters: |var_name|
-                Regex::new(r"\|([a-zA-Z_][a-zA-Z0-9_]*)\|").unwrap(),
-                // match patterns
-                Regex::new(r"\bSome\(([a-zA-Z_][a-zA-Z0-9_]*)\)").unwrap(),
-                Regex::new(r"\bOk\(([a-zA-Z_][a-zA-Z0-9_]*)\)").unwrap(),
-                Regex::new(r"\bErr\(([a-zA-Z_][a-zA-Z0-9_]*)\)").unwrap(),
-            ],
+            LanguagePattern::Python => return python::bindings(content),
+            LanguagePattern::Rust => return rust::bindings(content),
+            LanguagePattern::JavaScript => return javascript::bindings(content),
+            LanguagePattern::Java | LanguagePattern::CSharp => return java::bindings(content, self),
+            LanguagePattern::Kotlin => return kotlin::bindings(content),
+            _ => {}
+        }
+        let mut found = Vec::new();
+        for (kind, pattern) in self.get_variable_patterns() {
+            for cap in pattern.captures_iter(content) {
+                // Try to get the variable name from different capture groups
+                for var_match in cap.iter().skip(1).flatten() {
+                    found.push((var_match.start(), kind, var_match.as_str()));
+                }
+            }
+        }
+        found
+    }
+
+    pub fn get_variable_patterns(&self) -> Vec<(BindingKind, Regex)> {
+        use BindingKind::*;
+
+        // Where two patterns capture the same name at the same spot, the earlier one decides the kind
+        match self {
+            // Bound names are found by rust::bindings instead; see find_bindings
+            LanguagePattern::Rust => Vec::new(),
             
-            LanguagePattern::JavaScript => vec![
-                // let/const/var declarations
-                Regex::new(r"\b(?:let|const|var)\s+([a-zA-Z_$][a-zA-Z0-9_$]*)\s*[=;]").unwrap(),
-                // function parameters
-                Regex::new(r"\bfunction\s+[a-zA-Z_$][a-zA-Z0-9_$]*\s*\([^)]*\b([a-zA-Z_$][a-zA-Z0-9_$]*)\s*[,)]").unwrap(),
-                // arrow function parameters
-                Regex::new(r"\(([a-zA-Z_$][a-zA-Z0-9_$]*)\)\s*=>").unwrap(),
-                Regex::new(r"\b([a-zA-Z_$][a-zA-Z0-9_$]*)\s*=>").unwrap(),
-                // for loops
-                Regex::new(r"\bfor\s*\(\s*(?:let|const|var)?\s*([a-zA-Z_$][a-zA-Z0-9_$]*)\s+(?:in|of)\b").unwrap(),
-                // destructuring
-                Regex::new(r"\{\s*([a-zA-Z_$][a-zA-Z0-9_$]*)\s*\}").unwrap(),
-                Regex::new(r"\[\s*([a-zA-Z_$][a-zA-Z0-9_$]*)\s*\]").unwrap(),
-            ],
+            // Likewise javascript::bindings
+            LanguagePattern::JavaScript => Vec::new(),
             
-            LanguagePattern::Python => vec![
-                // variable assignment
-                Regex::new(r"^(\s*)([a-zA-Z_][a-zA-Z0-9_]*)\s*=").unwrap(),
-                // for loops
-                Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+in\b").unwrap(),
-                // function parameters
-                Regex::new(r"\bdef\s+[a-zA-Z_][a-zA-Z0-9_]*\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
-                // lambda parameters
-                Regex::new(r"\blambda\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*:").unwrap(),
-                // with statements
-                Regex::new(r"\bwith\s+[^)]+\s+as\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*:").unwrap(),
-                // except clauses
-                Regex::new(r"\bexcept\s+\w+\s+as\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*:").unwrap(),
-            ],
+            // Likewise python::bindings
+            LanguagePattern::Python => Vec::new(),
             
-            LanguagePattern::Java => vec![
-                // variable declarations
-                Regex::new(r"\b(?:int|long|short|byte|float|double|boolean|char|String|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;]").unwrap(),
-                // for loops
-                Regex::new(r"\bfor\s*\([^;]*;\s*[^;]*;\s*[^)]*\)\s*\{").unwrap(),
-                // enhanced for loops
-                Regex::new(r"\bfor\s*\(\s*\w+\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*:\s*[^)]+\)").unwrap(),
-                // method parameters
-                Regex::new(r"\b(?:public|private|protected|static)?\s*\w+\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
-                // catch blocks
-                Regex::new(r"\bcatch\s*\(\s*\w+\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*\)").unwrap(),
-            ],
+            // Likewise java::bindings
+            LanguagePattern::Java => Vec::new(),
             
             LanguagePattern::Cpp | LanguagePattern::C => vec![
-                // variable declarations
-                Regex::new(r"\b(?:int|long|short|char|float|double|bool|auto|const)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;,)]").unwrap(),
                 // for loops
-                Regex::new(r"\bfor\s*\([^;]*\b(?:int|auto)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;]").unwrap(),
+                (LoopIndex, Regex::new(r"\bfor\s*\([^;]*\b(?:int|auto)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;]").unwrap()),
+                // variable declarations
+                (Variable, Regex::new(r"\b(?:int|long|short|char|float|double|bool|auto|const)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;,)]").unwrap()),
                 // function parameters
-                Regex::new(r"\b\w+\s+\w+\s*\([^)]*\b\w+\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
+                (Parameter, Regex::new(r"\b\w+\s+\w+\s*\([^)]*\b\w+\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap()),
             ],
             
-            LanguagePattern::CSharp => vec![
-                // variable declarations
-                Regex::new(r"\b(?:int|long|short|byte|float|double|bool|string|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;]").unwrap(),
-                // foreach loops
-                Regex::new(r"\bforeach\s*\(\s*\w+\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+in\s+[^)]+\)").unwrap(),
-                // method parameters
-                Regex::new(r"\b(?:public|private|protected|internal)?\s*\w+\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
-            ],
+            // Likewise java::bindings, which reads C# too
+            LanguagePattern::CSharp => Vec::new(),
             
             LanguagePattern::Go => vec![
-                // variable declarations
-                Regex::new(r"\b(?:var\s+([a-zA-Z_][a-zA-Z0-9_]*)|([a-zA-Z_][a-zA-Z0-9_]*)\s*:=)").unwrap(),
                 // for loops
-                Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*(?::=|,)").unwrap(),
+                (LoopIndex, Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*(?::=|,)").unwrap()),
                 // function parameters
-                Regex::new(r"\bfunc\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s+\w+\s*[,)]").unwrap(),
+                (Parameter, Regex::new(r"\bfunc\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s+\w+\s*[,)]").unwrap()),
                 // range loops
-                Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*(?:,\s*[a-zA-Z_][a-zA-Z0-9_]*)?\s*:=\s*range").unwrap(),
+                (LoopIndex, Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*(?:,\s*[a-zA-Z_][a-zA-Z0-9_]*)?\s*:=\s*range").unwrap()),
+                // variable declarations
+                (Variable, Regex::new(r"\b(?:var\s+([a-zA-Z_][a-zA-Z0-9_]*)|([a-zA-Z_][a-zA-Z0-9_]*)\s*:=)").unwrap()),
             ],
             
             LanguagePattern::Ruby => vec![
                 // variable assignment
-                Regex::new(r"^(\s*)([a-zA-Z_][a-zA-Z0-9_]*)\s*=").unwrap(),
+                (Variable, Regex::new(r"^(\s*)([a-zA-Z_][a-zA-Z0-9_]*)\s*=").unwrap()),
                 // block parameters
-                Regex::new(r"\bdo\s*\|\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*\|").unwrap(),
-                Regex::new(r"\{\s*\|\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*\|").unwrap(),
+                (LambdaParam, Regex::new(r"\bdo\s*\|\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*\|").unwrap()),
+                (LambdaParam, Regex::new(r"\{\s*\|\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*\|").unwrap()),
                 // method parameters
-                Regex::new(r"\bdef\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
+                (Parameter, Regex::new(r"\bdef\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap()),
+                // rescue clauses
+                (ExceptionBinding, Regex::new(r"\brescue\b[^\n]*=>\s*([a-zA-Z_][a-zA-Z0-9_]*)").unwrap()),
             ],
             
             LanguagePattern::Php => vec![
+                // loop counters: for ($i = 0; ...)
+                (LoopIndex, Regex::new(r"\bfor\s*\(\s*\$([a-zA-Z_][a-zA-Z0-9_]*)\s*=").unwrap()),
                 // variable assignment (PHP variables start with $)
-                Regex::new(r"\$([a-zA-Z_][a-zA-Z0-9_]*)\s*=").unwrap(),
+                (Variable, Regex::new(r"\$([a-zA-Z_][a-zA-Z0-9_]*)\s*=").unwrap()),
                 // function parameters
-                Regex::new(r"\bfunction\s+\w+\s*\([^)]*\$([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
+                (Parameter, Regex::new(r"\bfunction\s+\w+\s*\([^)]*\$([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap()),
                 // foreach loops
-                Regex::new(r"\bforeach\s*\([^)]+\s+as\s+\$([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
+                (LoopIndex, Regex::new(r"\bforeach\s*\([^)]+\s+as\s+\$([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap()),
+                // catch blocks
+                (ExceptionBinding, Regex::new(r"\bcatch\s*\(\s*[\w\\]+\s+\$([a-zA-Z_][a-zA-Z0-9_]*)\s*\)").unwrap()),
             ],
             
-            LanguagePattern::Kotlin => vec![
-                // variable declarations
-                Regex::new(r"\b(?:val|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=:]").unwrap(),
-                // for loops
-                Regex::new(r"\bfor\s*\(\s*([a-zA-Z_][a-zA-Z0-9_]*)\s+in\s+[^)]+\)").unwrap(),
-                // function parameters
-                Regex::new(r"\bfun\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*:\s*\w+\s*[,)]").unwrap(),
-            ],
+            // Likewise kotlin::bindings
+            LanguagePattern::Kotlin => Vec::new(),
             
             LanguagePattern::Swift => vec![
                 // variable declarations
-                Regex::new(r"\b(?:let|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=:]").unwrap(),
+                (Variable, Regex::new(r"\b(?:let|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=:]").unwrap()),
                 // for loops
-                Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+in\s+").unwrap(),
+                (LoopIndex, Regex::new(r"\bfor\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+in\s+").unwrap()),
                 // function parameters
-                Regex::new(r"\bfunc\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*:\s*\w+\s*[,)]").unwrap(),
+                (Parameter, Regex::new(r"\bfunc\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*:\s*\w+\s*[,)]").unwrap()),
             ],
             
             LanguagePattern::Dart => vec![
+                // catch clauses: catch (e) / on Exception catch (e, stack)
+                (ExceptionBinding, Regex::new(r"\bcatch\s*\(\s*([a-zA-Z_][a-zA-Z0-9_]*)").unwrap()),
+                // loop counters: for (int i = 0; ...)
+                (LoopIndex, Regex::new(r"\bfor\s*\(\s*(?:int|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*=").unwrap()),
                 // variable declarations
-                Regex::new(r"\b(?:var|final|const|int|double|String|bool)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;]").unwrap(),
+                (Variable, Regex::new(r"\b(?:var|final|const|int|double|String|bool)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=;]").unwrap()),
                 // for loops
-                Regex::new(r"\bfor\s*\(\s*\w+\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+in\s+[^)]+\)").unwrap(),
+                (LoopIndex, Regex::new(r"\bfor\s*\(\s*\w+\s+([a-zA-Z_][a-zA-Z0-9_]*)\s+in\s+[^)]+\)").unwrap()),
                 // function parameters
-                Regex::new(r"\b\w+\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap(),
+                (Parameter, Regex::new(r"\b\w+\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*[,)]").unwrap()),
             ],
             
             LanguagePattern::Scala => vec![
                 // variable declarations
-                Regex::new(r"\b(?:val|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=:]").unwrap(),
+                (Variable, Regex::new(r"\b(?:val|var)\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*[=:]").unwrap()),
                 // for comprehensions
-                Regex::new(r"\bfor\s*\(\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*<-").unwrap(),
+                (LoopIndex, Regex::new(r"\bfor\s*\(\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*<-").unwrap()),
                 // function parameters
-                Regex::new(r"\bdef\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*:\s*\w+\s*[,)]").unwrap(),
+                (Parameter, Regex::new(r"\bdef\s+\w+\s*\([^)]*\b([a-zA-Z_][a-zA-Z0-9_]*)\s*:\s*\w+\s*[,)]").unwrap()),
             ],
         }
     }
+
+    pub fn comment_prefixes(&self) -> &'static [&'static str] {
+        match self {
+            LanguagePattern::Python | LanguagePattern::Ruby => &["#"],
+            LanguagePattern::Php => &["//", "#"],
+            _ => &["//"],
+        }
+    }
 }
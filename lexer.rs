@@ -0,0 +1,847 @@
+use crate::language_patterns::LanguagePattern;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TokenKind {
+    Ident,
+    Number,
+    // Including its quotes and any prefix: f"...", r#"..."#, b"...". Also JS regex
+    // literals, each piece of a template literal around its `${...}`s, and JSX text.
+    Str,
+    Char,
+    // Rust's 'a and 'label
+    Lifetime,
+    Comment,
+    Punct,
+    // JSX tags and attribute names: `<div`, `className`, `=`, `/>`
+    Markup,
+    // A line break outside strings and comments
+    Newline,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    // Byte offset into the content
+    pub offset: usize,
+    // 1-based
+    pub line: usize,
+}
+
+impl Token<'_> {
+    pub(crate) fn is(&self, text: &str) -> bool {
+        matches!(self.kind, TokenKind::Punct | TokenKind::Ident) && self.text == text
+    }
+
+    pub(crate) fn opens(&self) -> bool {
+        self.kind == TokenKind::Punct && matches!(self.text, "(" | "[" | "{")
+    }
+
+    pub(crate) fn closes(&self) -> bool {
+        self.kind == TokenKind::Punct && matches!(self.text, ")" | "]" | "}")
+    }
+}
+
+// Longest first, so `**=` isn't read as `**` and `=`. `<<` and `>>` are left out because
+// they also close nested generics.
+const OPERATORS: &[&str] = &[
+    "**=", "//=", "...", "..=", "===", "!==", "::", ":=", "->", "=>", "==", "!=", "<=", ">=", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "&&", "||", "**", "//", "..", "?.", "??",
+];
+
+struct Lexer<'a> {
+    content: &'a str,
+    language: &'a LanguagePattern,
+    pos: usize,
+    line: usize,
+    tokens: Vec<Token<'a>>,
+}
+
+// Splits content into tokens, well enough to tell code from strings and comments
+// and identifiers from everything else. Never fails: unterminated strings and
+// comments run to the end of the content.
+pub(crate) fn tokenize<'a>(content: &'a str, language: &'a LanguagePattern) -> Vec<Token<'a>> {
+    let mut lexer = Lexer { content, language, pos: 0, line: 1, tokens: Vec::new() };
+    while let Some(c) = lexer.peek(0) {
+        lexer.next_token(c);
+    }
+    lexer.tokens
+}
+
+impl<'a> Lexer<'a> {
+    fn next_token(&mut self, c: char) {
+        let start = self.pos;
+        let start_line = self.line;
+        let rest = &self.content[self.pos..];
+        let language = self.language;
+
+        let kind = if c == '\n' {
+            self.pos += 1;
+            self.line += 1;
+            TokenKind::Newline
+        } else if c.is_whitespace() {
+            self.pos += c.len_utf8();
+            return;
+        } else if c == '\\' && self.peek(1) == Some('\n') && *language == LanguagePattern::Python {
+            // An explicit line continuation joins the lines
+            self.pos += 2;
+            self.line += 1;
+            return;
+        } else if language.comment_prefixes().iter().any(|prefix| rest.starts_with(prefix)) {
+            self.pos += rest.find('\n').unwrap_or(rest.len());
+            TokenKind::Comment
+        } else if has_block_comments(language) && rest.starts_with("/*") {
+            self.pos += 2;
+            self.skip_until("*/", false);
+            TokenKind::Comment
+        } else if let Some((opening, closing, escapes)) = self.interpolated_string(rest) {
+            self.pos += opening;
+            self.interpolated(start, closing, escapes);
+            return;
+        } else if c == '/' && *language == LanguagePattern::JavaScript && self.expects_expression() {
+            self.regex();
+            TokenKind::Str
+        } else if c == '<' && *language == LanguagePattern::JavaScript && self.expects_expression() && self.starts_element() {
+            self.element();
+            return;
+        } else if let Some(prefix) = self.string_prefix(rest) {
+            self.pos += prefix;
+            self.string((*language == LanguagePattern::Rust && rest[..prefix].ends_with('r')) || *language == LanguagePattern::CSharp)
+        } else if c == '\'' && *language == LanguagePattern::Rust && !is_char_literal(rest) {
+            self.pos += 1;
+            let name = self.word_len();
+            self.pos += name;
+            if name == 0 {
+                TokenKind::Punct
+            } else {
+                TokenKind::Lifetime
+            }
+        } else if c == '"' || c == '\'' || (c == '`' && *language == LanguagePattern::Go) {
+            self.string(false)
+        } else if is_ident_start(c, language) {
+            self.pos += self.word_len();
+            TokenKind::Ident
+        } else if c.is_ascii_digit() {
+            self.number();
+            TokenKind::Number
+        } else {
+            self.pos += OPERATORS.iter().find(|op| rest.starts_with(*op)).map_or(c.len_utf8(), |op| op.len());
+            TokenKind::Punct
+        };
+        self.push(kind, start, start_line);
+    }
+
+    fn push(&mut self, kind: TokenKind, start: usize, line: usize) {
+        self.tokens.push(Token { kind, text: &self.content[start..self.pos], offset: start, line });
+    }
+
+    // 1e10 and 0x1f are numbers, not identifiers, and 1.5 is one number but 1..n is two tokens
+    fn number(&mut self) {
+        let digits = |rest: &str| rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        self.pos += digits(&self.content[self.pos..]);
+        let rest = &self.content[self.pos..];
+        if rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            self.pos += 1 + digits(&rest[1..]);
+        }
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.content[self.pos..].chars().nth(n)
+    }
+
+    fn word_len(&self) -> usize {
+        let rest = &self.content[self.pos..];
+        rest.find(|c: char| !is_ident_char(c, self.language)).unwrap_or(rest.len())
+    }
+
+    // The length of a prefix that belongs to the string after it: Python's f/r/b/u,
+    // Rust's b and raw strings' r#, C#'s verbatim @
+    fn string_prefix(&self, rest: &str) -> Option<usize> {
+        let letters = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let after = &rest[letters..];
+        match self.language {
+            LanguagePattern::Python if (1..=2).contains(&letters) && (after.starts_with('"') || after.starts_with('\'')) => {
+                rest[..letters].chars().all(|c| "rRbBuUfF".contains(c)).then_some(letters)
+            }
+            LanguagePattern::Rust if matches!(&rest[..letters], "b" | "r" | "br") => {
+                let hashes = after.len() - after.trim_start_matches('#').len();
+                let raw = rest[..letters].ends_with('r');
+                let quoted = after[hashes..].starts_with('"') || (!raw && hashes == 0 && after.starts_with('\''));
+                (quoted && (raw || hashes == 0)).then_some(letters)
+            }
+            LanguagePattern::CSharp if rest.starts_with("@\"") => Some(1),
+            _ => None,
+        }
+    }
+
+    // At an opening quote, or a Rust raw string's #s or C# verbatim string's quote
+    fn string(&mut self, raw: bool) -> TokenKind {
+        if raw {
+            let rest = &self.content[self.pos..];
+            let hashes = rest.len() - rest.trim_start_matches('#').len();
+            let closing = format!("\"{}", "#".repeat(hashes));
+            self.pos += hashes + 1;
+            self.skip_until(&closing, false);
+            return TokenKind::Str;
+        }
+        let rest = &self.content[self.pos..];
+        if has_triple_quotes(self.language) && (rest.starts_with("\"\"\"") || rest.starts_with("'''")) {
+            let closing = &rest[..3];
+            self.pos += 3;
+            self.skip_until(closing, true);
+            return TokenKind::Str;
+        }
+        let quote = self.peek(0).unwrap_or('"');
+        self.pos += 1;
+        self.skip_until(&quote.to_string(), quote != '`');
+        if quote == '\'' && has_char_literals(self.language) {
+            TokenKind::Char
+        } else {
+            TokenKind::Str
+        }
+    }
+
+    // Moves past the next `closing`, counting the lines on the way
+    fn skip_until(&mut self, closing: &str, escapes: bool) {
+        while let Some(c) = self.peek(0) {
+            if self.content[self.pos..].starts_with(closing) {
+                self.pos += closing.len();
+                return;
+            }
+            if c == '\\' && escapes {
+                self.pos += 1;
+                match self.peek(0) {
+                    Some(escaped) => self.advance(escaped),
+                    None => return,
+                }
+                continue;
+            }
+            self.advance(c);
+        }
+    }
+
+    fn advance(&mut self, c: char) {
+        self.line += (c == '\n') as usize;
+        self.pos += c.len_utf8();
+    }
+
+    // Whether a JS `/` or `<` here starts a regex or a JSX element rather than dividing
+    // or comparing: it does after an operator, an opening bracket or a keyword like `return`
+    fn expects_expression(&self) -> bool {
+        let previous = self.tokens.iter().rev().find(|token| !matches!(token.kind, TokenKind::Comment | TokenKind::Newline));
+        match previous {
+            None => true,
+            Some(token) if token.kind == TokenKind::Punct => !matches!(token.text, ")" | "]" | "}"),
+            Some(token) if token.kind == TokenKind::Ident => matches!(
+                token.text,
+                "return" | "typeof" | "case" | "do" | "else" | "in" | "of" | "new" | "delete" | "void" | "throw" | "yield" | "await"
+            ),
+            _ => false,
+        }
+    }
+
+    // At the `/` opening a regex literal: moves past its closing `/` and its flags
+    fn regex(&mut self) {
+        self.pos += 1;
+        // Inside a [...] class, where `/` doesn't end the regex
+        let mut class = false;
+        while let Some(c) = self.peek(0) {
+            match c {
+                '\n' => return,
+                '\\' => {
+                    self.pos += 1;
+                    if let Some(escaped) = self.peek(0).filter(|&escaped| escaped != '\n') {
+                        self.pos += escaped.len_utf8();
+                    }
+                    continue;
+                }
+                '[' => class = true,
+                ']' => class = false,
+                '/' if !class => {
+                    self.pos += 1;
+                    self.pos += self.word_len();
+                    return;
+                }
+                _ => {}
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    // The opening of a string with interpolations at rest, a JS template literal, a C#
+    // `$"..."` or any Kotlin string: the opening's length, the closing quote, and whether
+    // backslash escapes apply
+    fn interpolated_string(&self, rest: &str) -> Option<(usize, &'static str, bool)> {
+        match self.language {
+            LanguagePattern::JavaScript if rest.starts_with('`') => Some((1, "`", true)),
+            LanguagePattern::Kotlin if rest.starts_with("\"\"\"") => Some((3, "\"\"\"", false)),
+            LanguagePattern::Kotlin if rest.starts_with('"') => Some((1, "\"", true)),
+            LanguagePattern::CSharp => {
+                let prefix = &rest[..rest.len() - rest.trim_start_matches(['$', '@']).len()];
+                let quotes = &rest[prefix.len()..];
+                if !prefix.contains('$') {
+                    None
+                } else if quotes.starts_with("\"\"\"") {
+                    Some((prefix.len() + 3, "\"\"\"", false))
+                } else if quotes.starts_with('"') {
+                    Some((prefix.len() + 1, "\"", !prefix.contains('@')))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // After the opening of a string that started at start: pushes the text around each
+    // interpolation as a string and the code inside as tokens of its own. Interpolations
+    // are `${...}` in JS and Kotlin, Kotlin's `$name`, and `{...}` in C#, where `{{` is a brace.
+    fn interpolated(&mut self, start: usize, closing: &str, escapes: bool) {
+        let csharp = *self.language == LanguagePattern::CSharp;
+        let mut start = start;
+        let mut start_line = self.line;
+        while let Some(c) = self.peek(0) {
+            let rest = &self.content[self.pos..];
+            if c == '\\' && escapes {
+                self.pos += 1;
+                if let Some(escaped) = self.peek(0) {
+                    self.advance(escaped);
+                }
+                continue;
+            }
+            if rest.starts_with(closing) {
+                self.pos += closing.len();
+                break;
+            }
+            if csharp && rest.starts_with("{{") {
+                self.pos += 2;
+                continue;
+            }
+            if c == '$' && *self.language == LanguagePattern::Kotlin && self.peek(1).is_some_and(|next| is_ident_start(next, self.language)) {
+                self.pos += 1;
+                self.push(TokenKind::Str, start, start_line);
+                let name = self.pos;
+                self.pos += self.word_len();
+                self.push(TokenKind::Ident, name, self.line);
+                start = self.pos;
+                start_line = self.line;
+                continue;
+            }
+            let opening = if csharp && c == '{' {
+                1
+            } else if !csharp && rest.starts_with("${") {
+                2
+            } else {
+                0
+            };
+            if opening > 0 {
+                self.pos += opening;
+                self.push(TokenKind::Str, start, start_line);
+                self.embedded();
+                // The text resumes at the closing brace, or at a C# format spec
+                start = self.pos;
+                start_line = self.line;
+                if self.peek(0).is_some() {
+                    self.pos += 1;
+                }
+                continue;
+            }
+            self.advance(c);
+        }
+        self.push(TokenKind::Str, start, start_line);
+    }
+
+    // Tokenizes code up to the `}` closing the brace before it, and stops there; in C#,
+    // also at the `:` starting a format spec
+    fn embedded(&mut self) {
+        let mut depth = 0usize;
+        while let Some(c) = self.peek(0) {
+            match c {
+                '{' | '(' | '[' => depth += 1,
+                '}' if depth == 0 => return,
+                ':' if depth == 0 && *self.language == LanguagePattern::CSharp && self.peek(1) != Some(':') => return,
+                '}' | ')' | ']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.next_token(c);
+        }
+    }
+
+    // At a `<` where an expression is expected: whether it opens a JSX element rather
+    // than TypeScript generics (`<T>(x: T) => x`) or a type assertion (`<any>x`). It
+    // does if a matching `</name>` follows or the tag closes itself with `/>`.
+    fn starts_element(&self) -> bool {
+        let rest = &self.content[self.pos + 1..];
+        let name = &rest[..self.tag_name_len(rest)];
+        let tag = &rest[name.len()..];
+        if name.is_empty() {
+            // A fragment, `<>...</>`
+            return tag.starts_with('>') && tag.contains("</>");
+        }
+        if !name.starts_with(|c: char| is_ident_start(c, self.language)) || !tag.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            return false;
+        }
+        if tag.contains(&format!("</{}>", name)) {
+            return true;
+        }
+        // Otherwise the first `>` outside braces and quotes must end `/>`
+        let mut depth = 0usize;
+        let mut quote = None;
+        let mut previous = ' ';
+        for c in tag.chars() {
+            match (quote, c) {
+                (Some(open), _) if c == open => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '{') => depth += 1,
+                (None, '}') => depth = depth.saturating_sub(1),
+                (None, '>') if depth == 0 => return previous == '/',
+                _ => {}
+            }
+            previous = c;
+        }
+        false
+    }
+
+    // JSX names may have dashes, dots and a namespace: `aria-label`, `Foo.Bar`, `svg:rect`
+    fn tag_name_len(&self, rest: &str) -> usize {
+        rest.find(|c: char| !(is_ident_char(c, self.language) || matches!(c, '-' | '.' | ':'))).unwrap_or(rest.len())
+    }
+
+    // At the `<` of a JSX element: pushes its tags and attribute names as markup, its
+    // text and attribute values as strings, and the code in its braces as tokens
+    fn element(&mut self) {
+        // Elements open, this one included
+        let mut open = 0usize;
+        loop {
+            let closing = self.content[self.pos..].starts_with("</");
+            let self_closing = self.tag();
+            if closing {
+                open = open.saturating_sub(1);
+            } else if !self_closing {
+                open += 1;
+            }
+            if open == 0 {
+                return;
+            }
+            // The children, up to the next tag
+            let mut start = self.pos;
+            let mut start_line = self.line;
+            while let Some(c) = self.peek(0).filter(|&c| c != '<') {
+                if c == '{' {
+                    self.text(start, start_line);
+                    self.braces();
+                    start = self.pos;
+                    start_line = self.line;
+                } else {
+                    self.advance(c);
+                }
+            }
+            self.text(start, start_line);
+            if self.peek(0).is_none() {
+                return;
+            }
+        }
+    }
+
+    // Pushes JSX text from start, unless it's only whitespace
+    fn text(&mut self, start: usize, line: usize) {
+        if !self.content[start..self.pos].trim().is_empty() {
+            self.push(TokenKind::Str, start, line);
+        }
+    }
+
+    // At a JSX tag's `<` or `</`: moves past its `>`, returning whether it was `/>`
+    fn tag(&mut self) -> bool {
+        let start = self.pos;
+        self.pos += if self.content[self.pos..].starts_with("</") { 2 } else { 1 };
+        self.pos += self.tag_name_len(&self.content[self.pos..]);
+        self.push(TokenKind::Markup, start, self.line);
+        while let Some(c) = self.peek(0) {
+            let start = self.pos;
+            let start_line = self.line;
+            if c.is_whitespace() {
+                self.advance(c);
+                continue;
+            }
+            if c == '{' {
+                self.braces();
+                continue;
+            }
+            if c == '"' || c == '\'' {
+                let kind = self.string(false);
+                self.push(kind, start, start_line);
+                continue;
+            }
+            let end = self.content[self.pos..].starts_with("/>");
+            self.pos += match self.tag_name_len(&self.content[self.pos..]) {
+                0 if end => 2,
+                0 => c.len_utf8(),
+                name => name,
+            };
+            self.push(TokenKind::Markup, start, start_line);
+            if end || c == '>' {
+                return end;
+            }
+        }
+        false
+    }
+
+    // At a `{` in JSX: pushes it, the code inside and its `}`
+    fn braces(&mut self) {
+        let start = self.pos;
+        self.pos += 1;
+        self.push(TokenKind::Punct, start, self.line);
+        self.embedded();
+        if self.peek(0).is_some() {
+            let start = self.pos;
+            self.pos += 1;
+            self.push(TokenKind::Punct, start, self.line);
+        }
+    }
+}
+
+fn is_ident_start(c: char, language: &LanguagePattern) -> bool {
+    c.is_alphabetic() || c == '_' || (c == '$' && *language == LanguagePattern::JavaScript)
+}
+
+fn is_ident_char(c: char, language: &LanguagePattern) -> bool {
+    c.is_alphanumeric() || c == '_' || (c == '$' && *language == LanguagePattern::JavaScript)
+}
+
+fn has_block_comments(language: &LanguagePattern) -> bool {
+    !matches!(language, LanguagePattern::Python | LanguagePattern::Ruby)
+}
+
+fn has_triple_quotes(language: &LanguagePattern) -> bool {
+    matches!(
+        language,
+        LanguagePattern::Python
+            | LanguagePattern::Java
+            | LanguagePattern::CSharp
+            | LanguagePattern::Kotlin
+            | LanguagePattern::Swift
+            | LanguagePattern::Dart
+            | LanguagePattern::Scala
+    )
+}
+
+// Languages where 'x' is a single character rather than a string
+fn has_char_literals(language: &LanguagePattern) -> bool {
+    matches!(
+        language,
+        LanguagePattern::Rust
+            | LanguagePattern::Java
+            | LanguagePattern::Cpp
+            | LanguagePattern::C
+            | LanguagePattern::CSharp
+            | LanguagePattern::Go
+            | LanguagePattern::Kotlin
+            | LanguagePattern::Scala
+    )
+}
+
+// 'a', '\n' and '\u{1F600}' are chars; 'a and 'static are lifetimes
+fn is_char_literal(rest: &str) -> bool {
+    let mut chars = rest.chars().skip(1);
+    match chars.next() {
+        Some('\\') => true,
+        Some(_) => chars.next() == Some('\''),
+        None => false,
+    }
+}
+
+// Reserved words: never a variable, and a rename that produces one no longer names anything
+pub(crate) fn is_keyword(language: &LanguagePattern, word: &str) -> bool {
+    let keywords: &[&str] = match language {
+        LanguagePattern::Rust => &[
+            "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in",
+            "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct",
+            "super", "trait", "true", "type", "unsafe", "use", "where", "while", "async", "await", "dyn",
+        ],
+        LanguagePattern::Python => &[
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+            "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+        ],
+        LanguagePattern::Ruby => &[
+            "alias", "and", "begin", "break", "case", "class", "def", "defined", "do", "else", "elsif", "end", "ensure",
+            "false", "for", "if", "in", "module", "next", "nil", "not", "or", "redo", "rescue", "retry", "return",
+            "self", "super", "then", "true", "undef", "unless", "until", "when", "while", "yield",
+        ],
+        LanguagePattern::Go => &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "for", "func", "go",
+            "goto", "if", "import", "interface", "map", "package", "range", "return", "select", "struct", "switch",
+            "type", "var",
+        ],
+        LanguagePattern::Php => &[
+            "abstract", "and", "array", "as", "break", "case", "catch", "class", "clone", "const", "continue", "default",
+            "do", "echo", "else", "elseif", "extends", "final", "for", "foreach", "function", "global", "if",
+            "implements", "instanceof", "interface", "namespace", "new", "or", "private", "protected", "public",
+            "return", "static", "switch", "throw", "try", "use", "while",
+        ],
+        LanguagePattern::JavaScript => &[
+            "as", "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do", "else",
+            "export", "extends", "false", "finally", "for", "function", "if", "import", "in", "instanceof", "let", "new",
+            "null", "return", "super", "switch", "this", "throw", "true", "try", "typeof", "var", "void", "while",
+            "with", "yield", "async", "await",
+        ],
+        // The C family and the JVM/.NET languages share most of theirs
+        _ => &[
+            "abstract", "auto", "bool", "boolean", "break", "byte", "case", "catch", "char", "class", "const", "continue",
+            "default", "do", "double", "else", "enum", "extends", "extern", "false", "final", "finally", "float", "for",
+            "fun", "func", "goto", "if", "implements", "import", "in", "int", "interface", "is", "let", "long", "new",
+            "null", "object", "override", "package", "private", "protected", "public", "return", "short", "sizeof",
+            "static", "struct", "super", "switch", "this", "throw", "true", "try", "typedef", "union", "unsigned",
+            "val", "var", "void", "volatile", "when", "while",
+        ],
+    };
+    // And a few of their own
+    let own: &[&str] = match language {
+        LanguagePattern::Java => &["assert", "instanceof", "native", "strictfp", "synchronized", "throws", "transient"],
+        LanguagePattern::CSharp => &[
+            "as", "base", "checked", "delegate", "event", "explicit", "fixed", "foreach", "implicit", "internal", "lock",
+            "namespace", "operator", "out", "params", "readonly", "ref", "sealed", "stackalloc", "typeof", "unchecked",
+            "unsafe", "using", "virtual",
+        ],
+        LanguagePattern::Kotlin => &["as", "typealias"],
+        _ => &[],
+    };
+    keywords.contains(&word) || own.contains(&word)
+}
+
+// Index of the bracket closing the one at open, or tokens.len() if it's never closed, so
+// that tokens[open + 1..close] is always what's inside. Unfinished code, as an editor
+// sends it on every keystroke, is full of brackets that aren't closed yet.
+pub(crate) fn matching(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                return i;
+            }
+        }
+    }
+    tokens.len()
+}
+
+// Index of the bracket opening the one closed at close, or 0 if it's never opened, so
+// that tokens[open..=close] always holds both
+pub(crate) fn opening(tokens: &[Token], close: usize) -> usize {
+    let mut depth = 0usize;
+    for i in (0..=close.min(tokens.len().saturating_sub(1))).rev() {
+        if tokens[i].closes() {
+            depth += 1;
+        } else if tokens[i].opens() {
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                return i;
+            }
+        }
+    }
+    0
+}
+
+// Index of the first token outside brackets matching is, before the bracket tokens is in closes
+pub(crate) fn top_level(tokens: &[Token], is: impl Fn(&Token) -> bool) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            if depth == 0 {
+                return None;
+            }
+            depth -= 1;
+        } else if depth == 0 && is(token) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+// tokens split at its commas outside brackets
+pub(crate) fn split<'a, 'b>(tokens: &'b [Token<'a>]) -> Vec<&'b [Token<'a>]> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while let Some(comma) = top_level(&tokens[start..], |token| token.is(",")) {
+        parts.push(&tokens[start..start + comma]);
+        start += comma + 1;
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+// A parameter list split into parameters, at commas outside brackets and outside
+// type arguments: `Map<K, V> m, int x = 0`, `m: Map<K, V>, x: Int = 0`
+pub(crate) fn split_parameters<'a, 'b>(tokens: &'b [Token<'a>]) -> Vec<&'b [Token<'a>]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut angles = 0usize;
+    // In a default value, where `<` compares
+    let mut default = false;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.saturating_sub(1);
+        } else if depth > 0 {
+            continue;
+        } else if token.is(",") && (angles == 0 || default) {
+            parts.push(&tokens[start..i]);
+            start = i + 1;
+            angles = 0;
+            default = false;
+        } else if token.is("=") {
+            default = true;
+        } else if token.is("<") && !default {
+            angles += 1;
+        } else if token.is(">") && !default {
+            angles = angles.saturating_sub(1);
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+// Replaces each (offset, length) range of content with its text; ranges must not overlap
+pub(crate) fn apply_edits(content: &str, mut edits: Vec<(usize, usize, String)>) -> String {
+    edits.sort_by_key(|(offset, _, _)| *offset);
+    let mut result = String::with_capacity(content.len());
+    let mut copied = 0;
+    for (offset, length, text) in edits {
+        if offset < copied {
+            continue;
+        }
+        result.push_str(&content[copied..offset]);
+        result.push_str(&text);
+        copied = offset + length;
+    }
+    result.push_str(&content[copied..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (kind, text) of every token but line breaks
+    fn kinds<'a>(content: &'a str, language: &'a LanguagePattern) -> Vec<(TokenKind, &'a str)> {
+        tokenize(content, language).into_iter().filter(|token| token.kind != TokenKind::Newline).map(|token| (token.kind, token.text)).collect()
+    }
+
+    #[test]
+    fn strings_comments_and_operators() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("x **= f'{y}' + rb\"z\"  # y\n", &LanguagePattern::Python),
+            [(Ident, "x"), (Punct, "**="), (Str, "f'{y}'"), (Punct, "+"), (Str, "rb\"z\""), (Comment, "# y")]
+        );
+        assert_eq!(
+            kinds("a /* b */ = @\"c\\\" + d;", &LanguagePattern::CSharp),
+            [(Ident, "a"), (Comment, "/* b */"), (Punct, "="), (Str, "@\"c\\\""), (Punct, "+"), (Ident, "d"), (Punct, ";")]
+        );
+    }
+
+    #[test]
+    fn rust_lifetimes_chars_and_raw_strings() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("fn f<'a>(c: char) { 'x'; '\\n'; r#\"\"q\"\"#; 1..n; 1.5 }", &LanguagePattern::Rust)
+                .into_iter()
+                .filter(|(kind, _)| *kind != Punct)
+                .collect::<Vec<_>>(),
+            [
+                (Ident, "fn"),
+                (Ident, "f"),
+                (Lifetime, "'a"),
+                (Ident, "c"),
+                (Ident, "char"),
+                (Char, "'x'"),
+                (Char, "'\\n'"),
+                (Str, "r#\"\"q\"\"#"),
+                (Number, "1"),
+                (Ident, "n"),
+                (Number, "1.5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn javascript_regex_literals_and_division() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("a = b / c / d; r = /[/]x/g.test(s)", &LanguagePattern::JavaScript),
+            [
+                (Ident, "a"),
+                (Punct, "="),
+                (Ident, "b"),
+                (Punct, "/"),
+                (Ident, "c"),
+                (Punct, "/"),
+                (Ident, "d"),
+                (Punct, ";"),
+                (Ident, "r"),
+                (Punct, "="),
+                (Str, "/[/]x/g"),
+                (Punct, "."),
+                (Ident, "test"),
+                (Punct, "("),
+                (Ident, "s"),
+                (Punct, ")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn python_line_continuations_join_lines() {
+        let tokens = tokenize("x = \\\n    1\ny", &LanguagePattern::Python);
+        let lines: Vec<(&str, usize)> = tokens.iter().map(|token| (token.text, token.line)).collect();
+        assert_eq!(lines, [("x", 1), ("=", 1), ("1", 2), ("\n", 2), ("y", 3)]);
+    }
+
+    #[test]
+    fn brackets_and_parameters() {
+        let tokens = tokenize("(Map<K, V> m, int x = a < b, f(y, z))", &LanguagePattern::Java);
+        let close = matching(&tokens, 0);
+        assert_eq!(close, tokens.len() - 1);
+        assert_eq!(opening(&tokens, close), 0);
+        let parts: Vec<String> = split_parameters(&tokens[1..close])
+            .iter()
+            .map(|part| part.iter().map(|token| token.text).collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(parts, ["Map < K , V > m", "int x = a < b", "f ( y , z )"]);
+        assert_eq!(split(&tokens[1..close]).len(), 4);
+        assert_eq!(top_level(&tokens[1..], |token| token.is("y")), None);
+        assert_eq!(top_level(&tokens[1..], |token| token.is("m")), Some(6));
+
+        // TypeScript parameters, where a type follows the name
+        let tokens = tokenize("(m: Map<K, V>, [a, b] = [], x = a < b, ...rest)", &LanguagePattern::JavaScript);
+        let close = matching(&tokens, 0);
+        let parts: Vec<String> = split_parameters(&tokens[1..close])
+            .iter()
+            .map(|part| part.iter().map(|token| token.text).collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(parts, ["m : Map < K , V >", "[ a , b ] = [ ]", "x = a < b", "... rest"]);
+    }
+
+    #[test]
+    fn unclosed_brackets_end_at_the_end() {
+        let tokens = tokenize("f(a, (b", &LanguagePattern::Python);
+        assert_eq!(matching(&tokens, 1), tokens.len());
+        assert_eq!(matching(&tokens, 4), tokens.len());
+        assert!(tokens[5..matching(&tokens, 4)].iter().all(|token| token.text == "b"));
+        let tokens = tokenize("a)", &LanguagePattern::Python);
+        assert_eq!(opening(&tokens, 1), 0);
+    }
+
+    #[test]
+    fn edits_apply_in_order_and_skip_overlaps() {
+        let edits = vec![(4, 1, "bb".to_string()), (0, 1, "aa".to_string()), (4, 3, "zz".to_string())];
+        assert_eq!(apply_edits("a = b + c", edits), "aa = bb + c");
+    }
+}
@@ -0,0 +1,114 @@
+//! The engine behind the `bad_variable_changer` CLI, for embedding in other tools.
+//!
+//! [`detect_language`] picks the language from a file name, a [`Scanner`] finds bad
+//! names and returns them as [`Finding`]s, and a [`Rewriter`] renames them in source
+//! text. Anything worth telling the user comes back as a [`Warning`] rather than
+//! being printed. The public modules hold what a Scanner or Rewriter is set up from.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod baseline;
+pub mod cache;
+pub mod git;
+pub mod modifyfile;
+pub mod naming;
+mod java;
+mod javascript;
+mod kotlin;
+mod language_patterns;
+mod lexer;
+mod mapfile;
+mod python;
+mod rewriter;
+mod rules;
+mod rust;
+mod sanity;
+mod scanner;
+mod suggest;
+mod suppress;
+
+pub use language_patterns::{BindingKind, LanguagePattern};
+pub use mapfile::RenameMap;
+pub use rewriter::{find_collision, restore_variables, MappingScope, NamingStrategy, Rewrite, Rewriter};
+pub use rules::{bad_variable_rule, score_variable, RuleProfile, Score};
+pub use sanity::check_rewrite;
+pub use scanner::{Finding, Scanner};
+
+// Something a scan, rewrite or restore left alone or noticed along the way
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    // A `badvars:` comment that suppresses nothing (only with unused suppression reports on)
+    UnusedSuppression { line: usize, comment: String },
+    // A name from an applied map that clashes with something in the file
+    Rejected { old: String, new: String, reason: String },
+    // A restored name that more than one original was renamed to; candidates are sorted
+    Ambiguous { name: String, candidates: Vec<String> },
+    // A restored name whose original is in use again at line
+    NotRestored { new: String, old: String, line: usize },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UnusedSuppression { line, comment } => write!(f, "unused suppression at line {}: {}", line, comment),
+            Warning::Rejected { old, new, reason } => write!(f, "rejected {} -> {}: {}", old, new, reason),
+            Warning::Ambiguous { name, candidates } => write!(f, "{} could be any of {}; leaving it alone", name, candidates.join(", ")),
+            Warning::NotRestored { new, old, line } => {
+                write!(f, "not restoring {} -> {}: {} is already used again at line {}", new, old, old, line)
+            }
+        }
+    }
+}
+
+pub fn detect_language(file_path: &Path) -> Option<LanguagePattern> {
+    if let Some(extension) = file_path.extension() {
+        match extension.to_str()? {
+            "rs" => Some(LanguagePattern::Rust),
+            "js" | "jsx" | "ts" | "tsx" => Some(LanguagePattern::JavaScript),
+            "py" => Some(LanguagePattern::Python),
+            "java" => Some(LanguagePattern::Java),
+            "cpp" | "cc" | "cxx" | "c++" => Some(LanguagePattern::Cpp),
+            "c" | "h" => Some(LanguagePattern::C),
+            "cs" => Some(LanguagePattern::CSharp),
+            "go" => Some(LanguagePattern::Go),
+            "rb" => Some(LanguagePattern::Ruby),
+            "php" => Some(LanguagePattern::Php),
+            "kt" => Some(LanguagePattern::Kotlin),
+            "swift" => Some(LanguagePattern::Swift),
+            "dart" => Some(LanguagePattern::Dart),
+            "scala" => Some(LanguagePattern::Scala),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+// Source files in dir with a supported extension, sorted; hidden directories are skipped
+pub fn find_source_files(dir: &Path, recursive: bool) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() {
+                if detect_language(&path).is_some() {
+                    files.push(path);
+                }
+            } else if path.is_dir() && recursive && !is_hidden(&path) {
+                files.extend(find_source_files(&path, recursive));
+            }
+        }
+    }
+    
+    // read_dir order is platform-dependent; project-wide mappings need a stable order
+    files.sort();
+    files
+}
+
+// .git, .venv and friends are never searched
+pub fn is_hidden(dir: &Path) -> bool {
+    dir.file_name().unwrap_or_default().to_str().unwrap_or("").starts_with('.')
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use bad_variable_changer::{check_rewrite, detect_language, naming, LanguagePattern, MappingScope, NamingStrategy, Rewrite, Rewriter, RuleProfile, Scanner};
+
+// LSP DiagnosticSeverity.Warning
+const WARNING: u32 = 2;
+
+struct Server {
+    config: Config,
+    scanner: Scanner,
+    // Open documents by URI, with the text the editor last sent
+    documents: HashMap<String, String>,
+}
+
+// Serves diagnostics and rename code actions over stdio until the client says exit.
+// `config` is the one given with --config; without it, the workspace's .badvars.conf is used.
+pub fn run(config: Option<Config>) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server { scanner: Scanner::default(), config: Config::default(), documents: HashMap::new() };
+    let explicit_config = config.is_some();
+    if let Some(config) = config {
+        server.configure(config);
+    }
+
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let result = match method {
+            "initialize" => {
+                if !explicit_config {
+                    // The workspace root's config, or the one in the directory we were started in
+                    match workspace_config(params).unwrap_or_else(|| Config::discover(None)) {
+                        Ok(config) => server.configure(config),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                Some(json!({
+                    "capabilities": {
+                        // Full text on every change; files are small enough to rescan whole
+                        "textDocumentSync": 1,
+                        "codeActionProvider": true,
+                    },
+                    "serverInfo": { "name": "bad_variable_changer", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "shutdown" => Some(Value::Null),
+            "exit" => return Ok(()),
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                server.documents.insert(uri.to_string(), text.to_string());
+                server.publish(&mut output, uri)?;
+                None
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                // With full sync the last change holds the whole document
+                if let Some(text) = params["contentChanges"].as_array().and_then(|c| c.last()).and_then(|c| c["text"].as_str()) {
+                    server.documents.insert(uri.to_string(), text.to_string());
+                }
+                server.publish(&mut output, uri)?;
+                None
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                server.documents.remove(uri);
+                // Clear the squiggles of a closed file
+                send(&mut output, &json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": [] },
+                }))?;
+                None
+            }
+            "textDocument/codeAction" => Some(server.code_actions(params)),
+            _ => None,
+        };
+
+        // Only requests carry an id; notifications get no reply
+        let Some(id) = message.get("id") else {
+            continue;
+        };
+        let reply = match result {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("unsupported method: {}", method) },
+            }),
+        };
+        send(&mut output, &reply)?;
+    }
+    Ok(())
+}
+
+impl Server {
+    fn configure(&mut self, config: Config) {
+        self.scanner = Scanner::default()
+            .with_profile(config.profile.as_deref().and_then(RuleProfile::from_name).unwrap_or_default())
+            .with_threshold(config.threshold.unwrap_or(0));
+        self.config = config;
+    }
+
+    // (path, language, text) of an open document we know how to scan
+    fn document(&self, uri: &str) -> Option<(PathBuf, LanguagePattern, &str)> {
+        let text = self.documents.get(uri)?;
+        let path = uri_to_path(uri);
+        let language = detect_language(&path)?;
+        Some((path, language, text))
+    }
+
+    fn publish(&self, output: &mut impl Write, uri: &str) -> io::Result<()> {
+        let Some((path, language, text)) = self.document(uri) else {
+            return Ok(());
+        };
+        let diagnostics: Vec<Value> = self
+            .scanner
+            .scan(&path, text, &language)
+            .0
+            .into_iter()
+            .map(|finding| {
+                json!({
+                    "range": range(text, finding.offset, finding.offset + finding.name.len()),
+                    "severity": WARNING,
+                    "source": "badvars",
+                    "code": finding.rule,
+                    "message": format!("`{}` is a bad variable name ({})", finding.name, finding.score),
+                })
+            })
+            .collect();
+        send(output, &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    // A rename for each bad name used in the requested range, plus one for the whole file
+    fn code_actions(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some((path, language, text)) = self.document(uri) else {
+            return json!([]);
+        };
+        let generator = match naming::theme(self.config.theme.as_deref().unwrap_or("yourmom"), self.config.seed) {
+            Ok(generator) => generator,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return json!([]);
+            }
+        };
+        let strategy = self.config.strategy.as_deref().and_then(NamingStrategy::from_name).unwrap_or_default();
+        let mut rewriter = Rewriter::new(MappingScope::File, generator, strategy, None);
+        // Warnings have nowhere to go but the client, which doesn't ask for them
+        let Rewrite { content: rewritten, replacements: renames, .. } = rewriter.rewrite(&path, text, &language, &self.scanner);
+        if renames.is_empty() {
+            return json!([]);
+        }
+
+        // LSP lines are 0-based
+        let first_line = params["range"]["start"]["line"].as_u64().unwrap_or(0) as usize;
+        let last_line = params["range"]["end"]["line"].as_u64().map_or(usize::MAX, |line| line as usize);
+        let selected: Vec<&str> = text.lines().skip(first_line).take(last_line.saturating_sub(first_line) + 1).collect();
+        let selected = selected.join("\n");
+        let whole = range(text, 0, text.len());
+
+        let mut actions = Vec::new();
+        for (old, new) in &renames {
+            // Offered wherever the name is used, not just where it's declared
+            let mentioned = Regex::new(&format!(r"\b{}\b", regex::escape(old))).is_ok_and(|re| re.is_match(&selected));
+            if !mentioned {
+                continue;
+            }
+            let edited = Rewriter::apply(text, &language, &[(old.clone(), new.clone())]);
+            // Offering an edit that breaks the file is worse than offering none
+            if check_rewrite(text, &edited, &language).is_err() {
+                continue;
+            }
+            actions.push(json!({
+                "title": format!("Rename `{}` to `{}`", old, new),
+                "kind": "quickfix",
+                "edit": { "changes": { uri: [{ "range": whole, "newText": edited }] } },
+            }));
+        }
+        if (renames.len() > 1 || actions.is_empty()) && check_rewrite(text, &rewritten, &language).is_ok() {
+            actions.push(json!({
+                "title": format!("Rename all {} bad variable(s) in this file", renames.len()),
+                "kind": "quickfix",
+                "edit": { "changes": { uri: [{ "range": whole, "newText": rewritten }] } },
+            }));
+        }
+        Value::Array(actions)
+    }
+}
+
+// The workspace root's .badvars.conf, if the client told us the root and there is one
+fn workspace_config(params: &Value) -> Option<io::Result<Config>> {
+    let root = match params["rootUri"].as_str() {
+        Some(uri) => uri_to_path(uri),
+        None => PathBuf::from(params["rootPath"].as_str()?),
+    };
+    let config_path = root.join(crate::config::DEFAULT_CONFIG);
+    if !config_path.is_file() {
+        return None;
+    }
+    Some(Config::load(&config_path.display().to_string()))
+}
+
+// file:///home/me/a%20b.py -> /home/me/a b.py
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Path::new(&String::from_utf8_lossy(&decoded).into_owned()).to_path_buf()
+}
+
+// LSP positions count UTF-16 code units within a 0-based line
+fn position(text: &str, offset: usize) -> Value {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    json!({
+        "line": text[..offset].matches('\n').count(),
+        "character": text[line_start..offset].encode_utf16().count(),
+    })
+}
+
+fn range(text: &str, start: usize, end: usize) -> Value {
+    json!({ "start": position(text, start), "end": position(text, end) })
+}
+
+// One message: headers, a blank line, then Content-Length bytes of JSON. None at end of input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message without a Content-Length header"));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn send(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
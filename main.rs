@@ -1,178 +1,242 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
-use regex::Regex;
 use std::io;
+use std::process::Command;
 
-mod modifyfile;
-mod language_patterns;
-
-use language_patterns::LanguagePattern;
-
-fn get_yourmom_variation(index: usize) -> String {
-    let variations = vec![
-        "yourmom", "yOurMom", "YourMom", "yourMom", "YOURMOM",
-        "YouRmOm", "yOuRmOm", "YoUrMoM", "yourmOM", "YOURmom",
-        "YoUrMoThEr", "yourmother", "YourMother", "YOURMOTHER",
-        "yOuRmOtHeR", "yourmommy", "YourMommy", "YOURMOMMY",
-        "urmom", "UrMom", "URMOM", "yomama", "YoMama", "YOMAMA"
-    ];
-    variations[index % variations.len()].to_string()
-}
-
-fn is_bad_variable(var_name: &str) -> bool {
-    // Skip if it's already a yourmom variation
-    if var_name.to_lowercase().contains("yourmom") || 
-       var_name.to_lowercase().contains("yourmother") ||
-       var_name.to_lowercase().contains("urmom") ||
-       var_name.to_lowercase().contains("yomama") {
-        return false;
-    }
+use bad_variable_changer::baseline::{self, Baseline};
+use bad_variable_changer::cache::{self, Cache};
+use bad_variable_changer::git::{self, ChangeSet};
+use bad_variable_changer::{bad_variable_rule, check_rewrite, RenameMap, RuleProfile, Score};
+use bad_variable_changer::{detect_language, find_collision, find_source_files, modifyfile, naming, restore_variables};
+use bad_variable_changer::{MappingScope, NamingStrategy, Rewriter, Scanner, Warning};
+use bisect::Granularity;
+use config::Config;
+use journal::Journal;
 
-    let bad_patterns = vec![
-        // Single letter variables (a-z, but keep common ones like _ or $)
-        Regex::new(r"^[a-z]$").unwrap(),
-        // Common bad patterns
-        Regex::new(r"^(i|j|k|l|m|n|x|y|z|a|b|c|d|e|f|g|h)$").unwrap(),
-        // Generic terrible names
-        Regex::new(r"^(temp|tmp|var|val|data|item|elem|node|obj|thing|stuff)$").unwrap(),
-        // Variables with just numbers
-        Regex::new(r"^[a-z]+\d+$").unwrap(),
-        // Really short meaningless names
-        Regex::new(r"^(aa|bb|cc|dd|ee|ff|gg|hh|ii|jj|kk|ll|mm|nn|oo|pp|qq|rr|ss|tt|uu|vv|ww|xx|yy|zz)$").unwrap(),
-    ];
-    
-    bad_patterns.iter().any(|pattern| pattern.is_match(var_name))
-}
+mod bisect;
+mod config;
+mod interactive;
+mod journal;
+mod lsp;
+mod tui;
+#[cfg(target_os = "linux")]
+mod watch;
 
-fn detect_language(file_path: &Path) -> Option<LanguagePattern> {
-    if let Some(extension) = file_path.extension() {
-        match extension.to_str()? {
-            "rs" => Some(LanguagePattern::Rust),
-            "js" | "jsx" | "ts" | "tsx" => Some(LanguagePattern::JavaScript),
-            "py" => Some(LanguagePattern::Python),
-            "java" => Some(LanguagePattern::Java),
-            "cpp" | "cc" | "cxx" | "c++" => Some(LanguagePattern::Cpp),
-            "c" | "h" => Some(LanguagePattern::C),
-            "cs" => Some(LanguagePattern::CSharp),
-            "go" => Some(LanguagePattern::Go),
-            "rb" => Some(LanguagePattern::Ruby),
-            "php" => Some(LanguagePattern::Php),
-            "kt" => Some(LanguagePattern::Kotlin),
-            "swift" => Some(LanguagePattern::Swift),
-            "dart" => Some(LanguagePattern::Dart),
-            "scala" => Some(LanguagePattern::Scala),
-            _ => None,
-        }
-    } else {
-        None
-    }
-}
-
-fn extract_and_replace_variables(content: &str, language: &LanguagePattern) -> (String, HashMap<String, String>) {
-    let patterns = language.get_variable_patterns();
-    let mut replacements: HashMap<String, String> = HashMap::new();
-    let mut variation_counter = 0;
-    
-    // First pass: identify all bad variables
-    for pattern in &patterns {
-        for cap in pattern.captures_iter(content) {
-            // Try to get the variable name from different capture groups
-            for i in 1..cap.len() {
-                if let Some(var_match) = cap.get(i) {
-                    let var_name = var_match.as_str();
-                    if is_bad_variable(var_name) && !replacements.contains_key(var_name) {
-                        let replacement = get_yourmom_variation(variation_counter);
-                        replacements.insert(var_name.to_string(), replacement);
-                        variation_counter += 1;
-                    }
-                }
-            }
-        }
-    }
-    
-    // Second pass: replace all occurrences
-    let mut result = content.to_string();
-    for (old_name, new_name) in &replacements {
-        // Create a regex that matches the variable name with word boundaries
-        // But be careful with languages that use different identifier rules
-        let boundary_pattern = match language {
-            LanguagePattern::JavaScript | LanguagePattern::Python | LanguagePattern::Ruby => {
-                format!(r"\b{}\b", regex::escape(old_name))
-            }
-            _ => {
-                format!(r"\b{}\b", regex::escape(old_name))
-            }
-        };
-        
-        if let Ok(re) = Regex::new(&boundary_pattern) {
-            result = re.replace_all(&result, new_name.as_str()).to_string();
-        }
-    }
-    
-    (result, replacements)
-}
+// The renames made in each file of a run, as (file, [(old, new)])
+type RunReplacements = Vec<(String, Vec<(String, String)>)>;
 
-fn process_file(file_path: &Path, in_place: bool, backup: bool) -> io::Result<()> {
+fn process_file(file_path: &Path, in_place: bool, backup: bool, interactive: bool, rewriter: &mut Rewriter, scanner: &Scanner, journal: Option<&mut Journal>) -> io::Result<Vec<(String, String)>> {
     let language = detect_language(file_path);
     
     if language.is_none() {
         println!("Skipping {}: unsupported file type", file_path.display());
-        return Ok(());
+        return Ok(Vec::new());
     }
     
     let lang = language.unwrap();
     println!("Processing {} ({:?})", file_path.display(), lang);
     
     let content = modifyfile::read_file(file_path.to_str().unwrap())?;
-    let (modified_content, replacements) = extract_and_replace_variables(&content, &lang);
+    let rewrite = rewriter.rewrite(file_path, &content, &lang, scanner);
+    print_warnings(&rewrite.warnings);
+    let (mut modified_content, mut replacements, scores) = (rewrite.content, rewrite.replacements, rewrite.scores);
+    
+    if interactive && !replacements.is_empty() {
+        replacements = interactive::confirm_replacements(&content, &replacements, |name, chosen| find_collision(&content, name, chosen))?;
+        modified_content = Rewriter::apply(&content, &lang, &replacements);
+        println!();
+    }
     
     if replacements.is_empty() {
         println!("  No bad variables found.");
-        return Ok(());
+        return Ok(replacements);
     }
     
     println!("  Replaced variables:");
     for (old, new) in &replacements {
-        println!("    {} -> {}", old, new);
+        println!("    {}", describe_rename(old, new, &scores));
     }
     
+    if let Err(reason) = check_rewrite(&content, &modified_content, &lang) {
+        println!("  Not writing {}: {}", file_path.display(), reason);
+        return Ok(Vec::new());
+    }
+    write_output(file_path, &modified_content, in_place, backup, journal)?;
+    Ok(replacements)
+}
+
+// "tmp -> yourmom  (score 62: generic name +30, ...)"; mapped names no rule flags get no score
+fn describe_rename(old: &str, new: &str, scores: &HashMap<String, Score>) -> String {
+    match scores.get(old) {
+        Some(score) => format!("{} -> {}  ({})", old, new, score),
+        None => format!("{} -> {}", old, new),
+    }
+}
+
+// Printed to stderr, like errors, so they don't mix with the report
+fn print_warnings(warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("  Warning: {}", warning);
+    }
+}
+
+// With a journal, whatever is about to be overwritten or created is recorded first
+fn write_output(file_path: &Path, modified_content: &str, in_place: bool, backup: bool, mut journal: Option<&mut Journal>) -> io::Result<()> {
     if backup && in_place {
         let backup_path = format!("{}.backup", file_path.display());
+        if let Some(journal) = journal.as_deref_mut() {
+            journal.record(Path::new(&backup_path))?;
+        }
         fs::copy(file_path, &backup_path)?;
         println!("  Backup created: {}", backup_path);
     }
     
     if in_place {
-        modifyfile::write_file(file_path.to_str().unwrap(), &modified_content)?;
+        if let Some(journal) = journal {
+            journal.record(file_path)?;
+        }
+        modifyfile::write_file(file_path.to_str().unwrap(), modified_content)?;
         println!("  File modified in place.");
     } else {
         let output_file = format!("{}.fixed", file_path.display());
-        modifyfile::write_file(&output_file, &modified_content)?;
+        modifyfile::write_file(&output_file, modified_content)?;
         println!("  Modified content written to: {}", output_file);
     }
     
     Ok(())
 }
 
-fn find_source_files(dir: &Path, recursive: bool) -> Vec<PathBuf> {
-    let mut files = Vec::new();
-    
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_file() {
-                if detect_language(&path).is_some() {
-                    files.push(path);
-                }
-            } else if path.is_dir() && recursive && !path.file_name().unwrap_or_default().to_str().unwrap_or("").starts_with('.') {
-                files.extend(find_source_files(&path, recursive));
-            }
+// The renames written, per file
+fn review_files(files: Vec<PathBuf>, rewriter: &mut Rewriter, scanner: &Scanner, in_place: bool, backup: bool, mut journal: Option<&mut Journal>) -> io::Result<RunReplacements> {
+    let mut reviews = Vec::new();
+    for file_path in files {
+        let Some(language) = detect_language(&file_path) else {
+            continue;
+        };
+        let original = modifyfile::read_file(file_path.to_str().unwrap())?;
+        let rewrite = rewriter.rewrite(&file_path, &original, &language, scanner);
+        print_warnings(&rewrite.warnings);
+        let replacements = rewrite.replacements;
+        if replacements.is_empty() {
+            continue;
         }
+        let renames = replacements
+            .into_iter()
+            .map(|(old, new)| tui::Rename { rule: bad_variable_rule(&old).unwrap_or("mapped"), old, new, selected: true })
+            .collect();
+        reviews.push(tui::FileReview { path: file_path, language, original, renames });
     }
     
-    files
+    if reviews.is_empty() {
+        println!("No bad variables found.");
+        return Ok(Vec::new());
+    }
+    
+    if !tui::review(&mut reviews, Rewriter::apply)? {
+        println!("Review cancelled, no files were changed.");
+        return Ok(Vec::new());
+    }
+    
+    let mut applied = Vec::new();
+    for file in &reviews {
+        let selected = file.selected();
+        if selected.is_empty() {
+            continue;
+        }
+        println!("Applying {} rename(s) to {}", selected.len(), file.path.display());
+        let rewritten = Rewriter::apply(&file.original, &file.language, &selected);
+        if let Err(reason) = check_rewrite(&file.original, &rewritten, &file.language) {
+            println!("  Not writing {}: {}", file.path.display(), reason);
+            continue;
+        }
+        match write_output(&file.path, &rewritten, in_place, backup, journal.as_deref_mut()) {
+            Ok(()) => applied.push((file.path.display().to_string(), selected)),
+            Err(e) => eprintln!("Error processing {}: {}", file.path.display(), e),
+        }
+    }
+    println!("Processing complete!");
+    Ok(applied)
+}
+
+// Runs the --verify command once a run has written its files. If it fails, every file
+// the run wrote is rolled back from the journal. Returns whether it passed.
+fn verify_run(command: &str, journal: Journal, applied: &[(String, Vec<(String, String)>)]) -> io::Result<bool> {
+    if journal.is_empty() {
+        journal.commit()?;
+        return Ok(true);
+    }
+    println!("Verifying: {}", command);
+    let status = shell_command(command).status()?;
+    if status.success() {
+        journal.commit()?;
+        println!("Verification passed.");
+        return Ok(true);
+    }
+    
+    let rolled_back = journal.rollback()?;
+    let reason = status.code().map_or("killed by a signal".to_string(), |code| format!("exit status {}", code));
+    println!("Verification failed ({}); rolled back {} file(s). These renames had been applied:", reason, rolled_back);
+    for (file_path, replacements) in applied.iter().filter(|(_, replacements)| !replacements.is_empty()) {
+        let renames: Vec<String> = replacements.iter().map(|(old, new)| format!("{} -> {}", old, new)).collect();
+        println!("  {}: {}", file_path, renames.join(", "));
+    }
+    Ok(false)
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+#[cfg(target_os = "linux")]
+fn watch_files(paths: &[PathBuf], recursive: bool, scanner: &Scanner, fix: Option<&mut Rewriter>) -> io::Result<()> {
+    watch::run(paths, recursive, scanner, fix)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch_files(_paths: &[PathBuf], _recursive: bool, _scanner: &Scanner, _fix: Option<&mut Rewriter>) -> io::Result<()> {
+    eprintln!("Error: --watch is only supported on Linux");
+    Ok(())
+}
+
+fn restore_file(file_path: &Path, map: &RenameMap, in_place: bool, backup: bool, dry_run: bool) -> io::Result<()> {
+    let Some(language) = detect_language(file_path) else {
+        return Ok(());
+    };
+    let names = map.for_file(file_path);
+    let content = modifyfile::read_file(file_path.to_str().unwrap())?;
+    let restore = restore_variables(&content, &language, &names);
+    print_warnings(&restore.warnings);
+    let (restored_content, restored) = (restore.content, restore.replacements);
+    
+    if restored.is_empty() {
+        return Ok(());
+    }
+    
+    println!("{} {}:", if dry_run { "Would restore" } else { "Restoring" }, file_path.display());
+    for (new, old) in &restored {
+        println!("  {} -> {}", new, old);
+    }
+    
+    if let Err(reason) = check_rewrite(&content, &restored_content, &language) {
+        println!("  Not writing {}: {}", file_path.display(), reason);
+        return Ok(());
+    }
+    if !dry_run {
+        write_output(file_path, &restored_content, in_place, backup, None)?;
+    }
+    Ok(())
 }
 
 fn print_help() {
@@ -180,22 +244,68 @@ fn print_help() {
     println!();
     println!("Supports: Rust, JavaScript/TypeScript, Python, Java, C/C++, C#, Go, Ruby, PHP, Kotlin, Swift, Dart, Scala");
     println!();
-    let program_name = env::args().nth(0).unwrap_or("bad_variable_changer".to_string());
+    let program_name = env::args().next().unwrap_or("bad_variable_changer".to_string());
     println!("USAGE:");
-    println!("    {} [OPTIONS] <files_or_directories>", env::args().nth(0).unwrap_or("yourmom-fixer".to_string()));
+    println!("    {} [OPTIONS] <files_or_directories>", env::args().next().unwrap_or("yourmom-fixer".to_string()));
     println!();
     println!("OPTIONS:");
     println!("    -i, --in-place      Modify files in place instead of creating .fixed files");
     println!("    -r, --recursive     Process directories recursively");
     println!("    -b, --backup        Create .backup files when using --in-place");
     println!("    --dry-run           Show what would be changed without modifying files");
+    println!("    --interactive       Confirm, reject or edit each rename before it is applied");
+    println!("    --tui               Review all proposed renames in a full-screen terminal UI");
+    println!("    --watch             Keep running and rescan files as they're saved; with -i, fix them too");
+    println!("    --profile <name>    strict (default) flags every bad name; idiomatic allows i/j/k loop");
+    println!("                        indexes, e/err exception bindings and one-letter lambda and");
+    println!("                        comprehension variables");
+    println!("    --strategy <name>   yourmom (default) or suggest: derive real names from types,");
+    println!("                        assigned values, iterated collections and call sites");
+    println!("    --threshold <n>     Only rename names whose severity score is at least <n> (default 0)");
+    println!("    --theme <name|file> Names to hand out: yourmom (default), nato, greek, animals, pirate,");
+    println!("                        or a word list file with one word or phrase per line");
+    println!("    --seed <n>          Shuffle the theme's names with a fixed seed (same seed, same output)");
+    println!("    --config <file>     Read defaults from <file> instead of ./.badvars.conf");
+    println!("    --project           Give a bad name the same replacement in every file of the run");
+    println!("    --per-language      Like --project, but keep a separate mapping for each language");
+    println!("    --emit-map <file>   Write the old -> new mapping (per file and global) to <file>");
+    println!("    --apply-map <file>  Rename using a (hand-edited) mapping file instead of generated names");
+    println!("    --restore <file>    Rename replacements from a mapping file back to their original names");
+    println!("    --check             Report bad variables without modifying files; exit with status 1 if any");
+    println!("    --staged            Only look at files staged in git");
+    println!("    --since <rev>       Only look at files changed in git since <rev>");
+    println!("    --changed-lines     With --staged/--since, only flag names declared on changed lines");
+    println!("    --diff <file|->     Only flag names declared in lines a unified diff adds (- reads stdin)");
+    println!("    --write-baseline <file>  Record all current findings so later runs can ignore them");
+    println!("    --baseline <file>   Ignore findings recorded in a baseline file");
+    println!("    --report-unused-suppressions  Warn about badvars: comments that suppress nothing");
+    println!("    --no-cache          Scan every file from scratch instead of reusing .badvars-cache/");
+    println!("    --verify <command>  After an in-place run, run <command> (e.g. \"cargo check\"); if it fails,");
+    println!("                        put every modified file back and list the renames that were tried");
+    println!("    --bisect <unit>     With --verify, keep only the renames the command accepts, testing them");
+    println!("                        per identifier or per file and reporting the output of each rejection");
     println!("    -h, --help          Show this help message");
     println!();
+    println!("COMMANDS:");
+    println!("    install-hook [--force]  Install a git pre-commit hook running --check --staged --changed-lines");
+    println!("    lsp [--config <file>]   Run a language server on stdio: diagnostics and rename code actions");
+    println!("    cache clean             Delete the .badvars-cache/ directory");
+    println!();
     println!("EXAMPLES:");
     println!("    {} main.rs lib.py                    # Process specific files", program_name);
     println!("    {} -i -b src/                        # Process src/ in-place with backups", program_name);
     println!("    {} -r .                              # Process all source files recursively", program_name);
     println!("    {} --dry-run -r ./project            # Preview changes without modifying", program_name);
+    println!("    {} --check --staged --changed-lines  # Check only what is about to be committed", program_name);
+    println!("    git diff main | {} --check --diff -  # Check only names a diff introduces", program_name);
+    println!("    {} --check -r --baseline .badvars-baseline src/  # Fail only on new bad names", program_name);
+    println!();
+    println!("SUPPRESSION COMMENTS:");
+    println!("    x = 0  // badvars: ignore         Don't flag names declared on this line");
+    println!("    // badvars: ignore-next-line      Don't flag names declared on the next line");
+    println!("    // badvars: ignore-file           Don't flag anything in this file");
+    println!("    // badvars: allow(x, y)           Never flag x or y in this file");
+    println!("    (use # instead of // in Python and Ruby)");
     println!();
     println!("SUPPORTED EXTENSIONS:");
     println!("    .rs .js .jsx .ts .tsx .py .java .cpp .cc .cxx .c++ .c .h .cs .go .rb .php .kt .swift .dart .scala");
@@ -209,11 +319,88 @@ fn main() -> io::Result<()> {
         return Ok(());
     }
     
+    if args[1] == "cache" {
+        if args.get(2).map(String::as_str) != Some("clean") {
+            eprintln!("Error: the only cache command is 'cache clean'");
+            return Ok(());
+        }
+        let removed = Cache::clean(Path::new(cache::DEFAULT_CACHE_DIR))?;
+        println!("Removed {} cache entries from {}", removed, cache::DEFAULT_CACHE_DIR);
+        return Ok(());
+    }
+    
+    if args[1] == "install-hook" {
+        let force = args[2..].iter().any(|arg| arg == "--force");
+        let hook_path = git::install_hook(force)?;
+        println!("Pre-commit hook installed: {}", hook_path.display());
+        return Ok(());
+    }
+    
+    // Defaults come from the config file; options on the command line override them
+    let config_path = args.iter().position(|arg| arg == "--config").map(|i| args.get(i + 1));
+    let config = match config_path {
+        Some(None) => {
+            eprintln!("Error: --config requires a file argument");
+            return Ok(());
+        }
+        Some(Some(path)) => Config::discover(Some(path)),
+        None => Config::discover(None),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Ok(());
+        }
+    };
+    
+    if args[1] == "lsp" {
+        // Without --config the server looks in the workspace the editor opens
+        return lsp::run(config_path.is_some().then_some(config));
+    }
+    
     let mut in_place = false;
     let mut recursive = false;
     let mut backup = false;
     let mut dry_run = false;
-    let mut paths = Vec::new();
+    let mut interactive = false;
+    let mut review = false;
+    let mut watch = false;
+    let mut no_cache = false;
+    let mut verify: Option<String> = None;
+    let mut bisect: Option<Granularity> = None;
+    let mut seed = config.seed;
+    let mut theme = config.theme.clone().unwrap_or_else(|| "yourmom".to_string());
+    let mut scope = MappingScope::File;
+    let mut emit_map: Option<String> = None;
+    let mut apply_map: Option<String> = None;
+    let mut restore_map: Option<String> = None;
+    let mut check = false;
+    let mut staged = false;
+    let mut since: Option<String> = None;
+    let mut changed_lines_only = false;
+    let mut diff_source: Option<String> = None;
+    let mut baseline_path: Option<String> = None;
+    let mut write_baseline: Option<String> = None;
+    let mut report_unused_suppressions = false;
+    let mut profile = match config.profile.as_deref().map(RuleProfile::from_name) {
+        Some(Some(p)) => p,
+        Some(None) => {
+            eprintln!("Error: profile in the config file must be 'strict' or 'idiomatic'");
+            return Ok(());
+        }
+        None => RuleProfile::default(),
+    };
+    let mut threshold = config.threshold.unwrap_or(0);
+    let mut strategy = match config.strategy.as_deref().map(NamingStrategy::from_name) {
+        Some(Some(s)) => s,
+        Some(None) => {
+            eprintln!("Error: strategy in the config file must be 'yourmom' or 'suggest'");
+            return Ok(());
+        }
+        None => NamingStrategy::default(),
+    };
+    let mut paths: Vec<&str> = Vec::new();
     
     // Parse arguments
     let mut i = 1;
@@ -227,6 +414,115 @@ fn main() -> io::Result<()> {
             "-r" | "--recursive" => recursive = true,
             "-b" | "--backup" => backup = true,
             "--dry-run" => dry_run = true,
+            "--interactive" => interactive = true,
+            "--tui" => review = true,
+            "--watch" => watch = true,
+            "--no-cache" => no_cache = true,
+            "--check" => check = true,
+            "--staged" => staged = true,
+            "--changed-lines" => changed_lines_only = true,
+            "--report-unused-suppressions" => report_unused_suppressions = true,
+            "--since" => {
+                i += 1;
+                let Some(rev) = args.get(i) else {
+                    eprintln!("Error: --since requires a git revision");
+                    return Ok(());
+                };
+                since = Some(rev.clone());
+            }
+            "--diff" => {
+                i += 1;
+                let Some(source) = args.get(i) else {
+                    eprintln!("Error: --diff requires a file, or - for stdin");
+                    return Ok(());
+                };
+                diff_source = Some(source.clone());
+            }
+            "--project" => scope = MappingScope::Project,
+            "--per-language" => scope = MappingScope::Language,
+            "--emit-map" | "--apply-map" | "--restore" | "--baseline" | "--write-baseline" => {
+                let option = args[i].clone();
+                i += 1;
+                let Some(map_path) = args.get(i) else {
+                    eprintln!("Error: {} requires a file argument", option);
+                    return Ok(());
+                };
+                match option.as_str() {
+                    "--emit-map" => emit_map = Some(map_path.clone()),
+                    "--apply-map" => apply_map = Some(map_path.clone()),
+                    "--baseline" => baseline_path = Some(map_path.clone()),
+                    "--write-baseline" => write_baseline = Some(map_path.clone()),
+                    _ => restore_map = Some(map_path.clone()),
+                }
+            }
+            "--profile" => {
+                i += 1;
+                match args.get(i).and_then(|name| RuleProfile::from_name(name)) {
+                    Some(p) => profile = p,
+                    None => {
+                        eprintln!("Error: --profile must be 'strict' or 'idiomatic'");
+                        return Ok(());
+                    }
+                }
+            }
+            "--strategy" => {
+                i += 1;
+                match args.get(i).and_then(|name| NamingStrategy::from_name(name)) {
+                    Some(s) => strategy = s,
+                    None => {
+                        eprintln!("Error: --strategy must be 'yourmom' or 'suggest'");
+                        return Ok(());
+                    }
+                }
+            }
+            "--verify" => {
+                i += 1;
+                let Some(command) = args.get(i) else {
+                    eprintln!("Error: --verify requires a command, e.g. --verify \"cargo check\"");
+                    return Ok(());
+                };
+                verify = Some(command.clone());
+            }
+            "--bisect" => {
+                i += 1;
+                match args.get(i).and_then(|name| Granularity::from_name(name)) {
+                    Some(g) => bisect = Some(g),
+                    None => {
+                        eprintln!("Error: --bisect must be 'identifier' or 'file'");
+                        return Ok(());
+                    }
+                }
+            }
+            "--theme" => {
+                i += 1;
+                let Some(name) = args.get(i) else {
+                    eprintln!("Error: --theme requires one of {} or a word list file", naming::THEMES.join(", "));
+                    return Ok(());
+                };
+                theme = name.clone();
+            }
+            // Already loaded above
+            "--config" => i += 1,
+            "--threshold" => {
+                i += 1;
+                match args.get(i).and_then(|s| s.parse::<u32>().ok()) {
+                    Some(n) => threshold = n,
+                    None => {
+                        eprintln!("Error: --threshold requires a non-negative integer");
+                        return Ok(());
+                    }
+                }
+            }
+            "--seed" => {
+                i += 1;
+                match args.get(i).and_then(|s| s.parse::<u64>().ok()) {
+                    Some(n) => seed = Some(n),
+                    None => {
+                        eprintln!("Error: --seed requires a non-negative integer");
+                        return Ok(());
+                    }
+                }
+            }
             arg => {
                 if arg.starts_with('-') {
                     eprintln!("Unknown option: {}", arg);
@@ -238,19 +534,97 @@ fn main() -> io::Result<()> {
         i += 1;
     }
     
-    if paths.is_empty() {
+    let changes = match (&since, staged) {
+        (Some(rev), _) => Some(ChangeSet::Since(rev)),
+        (None, true) => Some(ChangeSet::Staged),
+        (None, false) => None,
+    };
+    // Checking the index rather than the working tree; --since wins over --staged
+    let staged = matches!(changes, Some(ChangeSet::Staged));
+    
+    if paths.is_empty() && changes.is_none() && diff_source.is_none() {
         eprintln!("Error: No input files or directories specified.");
         print_help();
         return Ok(());
     }
     
-    if dry_run {
+    if changed_lines_only && changes.is_none() {
+        eprintln!("Error: --changed-lines needs --staged or --since <rev>");
+        return Ok(());
+    }
+    
+    if watch && (changes.is_some() || diff_source.is_some() || paths.is_empty()) {
+        eprintln!("Error: --watch needs files or directories, and can't be combined with --staged, --since or --diff");
+        return Ok(());
+    }
+    let watch_paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    
+    // Only in-place rewrites change what the command sees
+    if verify.is_some() && (!in_place || dry_run || check || watch) {
+        eprintln!("Error: --verify needs --in-place, and can't be combined with --dry-run, --check or --watch");
+        return Ok(());
+    }
+    if bisect.is_some() && (verify.is_none() || interactive || review) {
+        eprintln!("Error: --bisect needs --verify, and can't be combined with --interactive or --tui");
+        return Ok(());
+    }
+    // The review applies what's accepted, which neither mode may do
+    if review && (check || dry_run) {
+        eprintln!("Error: --tui can't be combined with --check or --dry-run");
+        return Ok(());
+    }
+    
+    // Check mode never writes; it reports like a dry run
+    if check {
+        dry_run = true;
+    } else if dry_run {
         println!("DRY RUN MODE - No files will be modified\n");
     }
     
+    let changed = match (&diff_source, &changes, changed_lines_only) {
+        (Some(source), _, _) => Some(git::read_diff(source)?),
+        (None, Some(changes), true) => Some(git::changed_lines(changes)?),
+        _ => None,
+    };
+    
+    let mut scanner = Scanner::default()
+        .with_unused_suppressions(report_unused_suppressions)
+        .with_profile(profile)
+        .with_threshold(threshold);
+    if let Some(changed) = changed {
+        scanner = scanner.with_changed_lines(changed);
+    }
+    // A new baseline records everything, including what the old one covered
+    if let (Some(path), None) = (&baseline_path, &write_baseline) {
+        scanner = scanner.with_baseline(Baseline::load(path)?);
+    }
+    if !no_cache {
+        scanner = scanner.with_cache(Cache::new(cache::DEFAULT_CACHE_DIR));
+    }
+    
     let mut all_files = Vec::new();
     
     // Collect all files to process
+    let candidates = match (&changes, scanner.changed_lines()) {
+        (Some(changes), _) => Some(git::changed_files(changes)?),
+        (None, Some(changed)) if diff_source.is_some() => {
+            let mut files: Vec<PathBuf> = changed.keys().filter(|path| path.is_file()).cloned().collect();
+            files.sort();
+            Some(files)
+        }
+        _ => None,
+    };
+    if let Some(candidates) = candidates {
+        // Only changed files, narrowed to the given paths if there are any
+        let roots: Vec<PathBuf> = paths.iter().map(|p| git::canonical(Path::new(p))).collect();
+        for file_path in candidates {
+            let canonical = git::canonical(&file_path);
+            if detect_language(&file_path).is_some() && (roots.is_empty() || roots.iter().any(|root| canonical.starts_with(root))) {
+                all_files.push(file_path);
+            }
+        }
+        paths.clear();
+    }
     for path_str in paths {
         let path = Path::new(path_str);
         if path.is_file() {
@@ -262,39 +636,142 @@ fn main() -> io::Result<()> {
         }
     }
     
-    if all_files.is_empty() {
+    // A watched directory may well be empty for now
+    if all_files.is_empty() && !watch {
         println!("No supported source files found.");
         return Ok(());
     }
     
-    println!("Found {} files to process\n", all_files.len());
+    if !check {
+        println!("Found {} files to process\n", all_files.len());
+    }
+    
+    
+    if let Some(path) = &write_baseline {
+        let mut entries = Vec::new();
+        for file_path in &all_files {
+            let Some(lang) = detect_language(file_path) else {
+                continue;
+            };
+            let content = if staged { git::staged_content(file_path)? } else { modifyfile::read_file(file_path.to_str().unwrap())? };
+            let file_key = baseline::file_key(file_path);
+            let (findings, warnings) = scanner.scan(file_path, &content, &lang);
+            print_warnings(&warnings);
+            for finding in findings {
+                let fingerprint = baseline::fingerprint(&content, finding.offset, &finding.name);
+                entries.push((file_key.clone(), finding.name, fingerprint));
+            }
+        }
+        Baseline::save(&entries, path)?;
+        println!("Baseline with {} finding(s) written to: {}", entries.len(), path);
+        return Ok(());
+    }
+    
+    if let Some(map_path) = &restore_map {
+        let map = RenameMap::load(map_path)?;
+        for file_path in all_files {
+            if let Err(e) = restore_file(&file_path, &map, in_place, backup, dry_run) {
+                eprintln!("Error restoring {}: {}", file_path.display(), e);
+            }
+        }
+        println!("Restore complete!");
+        return Ok(());
+    }
     
-    for file_path in all_files {
-        if dry_run {
-            // For dry run, just show what would be replaced
-            if let Some(lang) = detect_language(&file_path) {
-                if let Ok(content) = fs::read_to_string(&file_path) {
-                    let (_, replacements) = extract_and_replace_variables(&content, &lang);
-                    if !replacements.is_empty() {
-                        println!("Would process {} ({:?}):", file_path.display(), lang);
-                        for (old, new) in replacements {
-                            println!("  {} -> {}", old, new);
+    let applied = match &apply_map {
+        Some(map_path) => Some(RenameMap::load(map_path)?),
+        None => None,
+    };
+    let generator = match naming::theme(&theme, seed) {
+        Ok(generator) => generator,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Ok(());
+        }
+    };
+    let mut rewriter = Rewriter::new(scope, generator, strategy, applied);
+    let mut run_replacements: RunReplacements = Vec::new();
+    
+    if watch {
+        // -i fixes files as they're saved; otherwise findings are only reported
+        return watch_files(&watch_paths, recursive, &scanner, in_place.then_some(&mut rewriter));
+    }
+    
+    let mut journal = match verify.as_ref().map(|_| Journal::begin()) {
+        Some(Ok(journal)) => Some(journal),
+        Some(Err(e)) => {
+            eprintln!("Error: {}", e);
+            return Ok(());
+        }
+        None => None,
+    };
+    
+    if review {
+        let applied = review_files(all_files, &mut rewriter, &scanner, in_place, backup, journal.as_mut())?;
+        if let (Some(command), Some(journal)) = (&verify, journal) {
+            if !verify_run(command, journal, &applied)? {
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    
+    if let (Some(granularity), Some(command), Some(journal)) = (bisect, &verify, journal.take_if(|_| bisect.is_some())) {
+        // Whatever is left after bisecting has already passed the verify command
+        run_replacements = bisect::run(&all_files, &mut rewriter, &scanner, command, granularity, backup, journal)?;
+    } else {
+        for file_path in all_files {
+            if dry_run {
+                // For dry run, just show what would be replaced
+                if let Some(lang) = detect_language(&file_path) {
+                    // With --staged, what is about to be committed; unstaged edits don't count
+                    let content = if staged { git::staged_content(&file_path) } else { fs::read_to_string(&file_path) };
+                    if let Ok(content) = content {
+                        let rewrite = rewriter.rewrite(&file_path, &content, &lang, &scanner);
+                        print_warnings(&rewrite.warnings);
+                        let replacements = rewrite.replacements;
+                        if !replacements.is_empty() {
+                            let verb = if check { "Bad variables in" } else { "Would process" };
+                            println!("{} {} ({:?}):", verb, file_path.display(), lang);
+                            for (old, new) in &replacements {
+                                println!("  {}", describe_rename(old, new, &rewrite.scores));
+                            }
+                            println!();
                         }
+                        run_replacements.push((file_path.display().to_string(), replacements));
                     }
                 }
-            }
-        } else {
-            match process_file(&file_path, in_place, backup) {
-                Ok(()) => {}
-                Err(e) => {
-                    eprintln!("Error processing {}: {}", file_path.display(), e);
+            } else {
+                match process_file(&file_path, in_place, backup, interactive, &mut rewriter, &scanner, journal.as_mut()) {
+                    Ok(replacements) => run_replacements.push((file_path.display().to_string(), replacements)),
+                    Err(e) => {
+                        eprintln!("Error processing {}: {}", file_path.display(), e);
+                    }
                 }
+                println!();
             }
         }
-        println!();
     }
     
-    if dry_run {
+    if let (Some(command), Some(journal)) = (&verify, journal) {
+        if !verify_run(command, journal, &run_replacements)? {
+            std::process::exit(1);
+        }
+    }
+    
+    let found: usize = run_replacements.iter().map(|(_, replacements)| replacements.len()).sum();
+    if let Some(map_path) = &emit_map {
+        RenameMap::from_run(run_replacements, scope != MappingScope::File).save(map_path)?;
+        println!("Rename map written to: {}", map_path);
+    }
+    
+    if check {
+        if found > 0 {
+            println!("{} bad variable name(s) found.", found);
+            std::process::exit(1);
+        }
+        println!("No bad variable names found.");
+    } else if dry_run {
         println!("Dry run complete. Use without --dry-run to apply changes.");
     } else {
         println!("Processing complete!");
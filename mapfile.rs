@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::fs::File;
+use std::path::Path;
+
+use crate::baseline;
+use crate::modifyfile;
+
+// A reviewable old -> new mapping. Plain text so it can be edited by hand:
+//
+//     [global]
+//     tmp -> yourmom
+//
+//     [src/main.py]
+//     i -> yOurMom
+//
+// Entries in a file section win over [global] for that file. Sections are keyed by
+// baseline::file_key, so `./src/main.py` and `src/main.py` are the same file.
+#[derive(Debug, Default)]
+pub struct RenameMap {
+    pub global: Vec<(String, String)>,
+    pub files: Vec<(String, Vec<(String, String)>)>,
+}
+
+const GLOBAL_SECTION: &str = "global";
+
+impl RenameMap {
+    // Builds a map from the replacements of a run, one section per file. With a mapping
+    // shared between files (--project, --per-language), a name that got the same
+    // replacement in every file it appeared in, and appeared in more than one, moves to
+    // [global] instead.
+    pub fn from_run(files: Vec<(String, Vec<(String, String)>)>, shared: bool) -> Self {
+        let mut files: Vec<(String, Vec<(String, String)>)> =
+            files.into_iter().map(|(path, replacements)| (baseline::file_key(Path::new(&path)), replacements)).collect();
+        if !shared {
+            return RenameMap { global: Vec::new(), files };
+        }
+        // Each old name's replacement, or None once two files disagree, and how many files it's in
+        let mut seen: Vec<(String, Option<String>, usize)> = Vec::new();
+        for (_, replacements) in &files {
+            for (old, new) in replacements {
+                match seen.iter_mut().find(|(o, _, _)| o == old) {
+                    Some((_, agreed, count)) => {
+                        if agreed.as_ref() != Some(new) {
+                            *agreed = None;
+                        }
+                        *count += 1;
+                    }
+                    None => seen.push((old.clone(), Some(new.clone()), 1)),
+                }
+            }
+        }
+        let global: Vec<(String, String)> =
+            seen.into_iter().filter(|(_, _, count)| *count > 1).filter_map(|(old, agreed, _)| Some((old, agreed?))).collect();
+        for (_, replacements) in &mut files {
+            replacements.retain(|entry| !global.contains(entry));
+        }
+        RenameMap { global, files }
+    }
+
+    // The renames for file_path: its own section's, plus the [global] ones for names the
+    // section doesn't list and replacements it doesn't already use
+    pub fn for_file(&self, file_path: &Path) -> HashMap<String, String> {
+        let key = baseline::file_key(file_path);
+        let own: Vec<&(String, String)> =
+            self.files.iter().filter(|(path, _)| baseline::file_key(Path::new(path)) == key).flat_map(|(_, entries)| entries).collect();
+        let shadowed = |(old, new): &&(String, String)| own.iter().any(|(o, n)| o == old || n == new);
+        self.global.iter().filter(|entry| !shadowed(entry)).chain(own.iter().copied()).cloned().collect()
+    }
+
+    pub fn load(map_path: &str) -> io::Result<Self> {
+        let content = modifyfile::read_file(map_path)?;
+        let mut map = RenameMap::default();
+        let mut section: Option<String> = None;
+
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line[1..line.len() - 1].trim().to_string();
+                if name != GLOBAL_SECTION {
+                    map.files.push((name.clone(), Vec::new()));
+                }
+                section = Some(name);
+                continue;
+            }
+
+            let entry = match line.split_once("->") {
+                Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+                    (old.trim().to_string(), new.trim().to_string())
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}:{}: expected 'old -> new', got '{}'", map_path, line_no + 1, line),
+                    ));
+                }
+            };
+            match section.as_deref() {
+                Some(GLOBAL_SECTION) => map.global.push(entry),
+                Some(_) => map.files.last_mut().unwrap().1.push(entry),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}:{}: mapping entry before any [section]", map_path, line_no + 1),
+                    ));
+                }
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn save(&self, map_path: &str) -> io::Result<()> {
+        let mut file = File::create(map_path)?;
+        writeln!(file, "# bad_variable_changer rename map")?;
+        writeln!(file, "# Edit the right-hand side or delete lines, then use --apply-map")?;
+        if !self.global.is_empty() {
+            writeln!(file)?;
+            writeln!(file, "[{}]", GLOBAL_SECTION)?;
+            for (old, new) in &self.global {
+                writeln!(file, "{} -> {}", old, new)?;
+            }
+        }
+        for (path, entries) in &self.files {
+            if entries.is_empty() {
+                continue;
+            }
+            writeln!(file)?;
+            writeln!(file, "[{}]", path)?;
+            for (old, new) in entries {
+                writeln!(file, "{} -> {}", old, new)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(old, new)| (old.to_string(), new.to_string())).collect()
+    }
+
+    #[test]
+    fn file_scope_keeps_every_entry_in_its_file() {
+        let map = RenameMap::from_run(vec![("./a.py".to_string(), entries(&[("tmp", "yourmom")])), ("b.py".to_string(), entries(&[("x", "yourmom")]))], false);
+        assert!(map.global.is_empty());
+        assert_eq!(map.for_file(Path::new("a.py")), entries(&[("tmp", "yourmom")]).into_iter().collect());
+        assert_eq!(map.for_file(Path::new("./b.py")), entries(&[("x", "yourmom")]).into_iter().collect());
+    }
+
+    #[test]
+    fn shared_scope_lists_names_in_several_files_once() {
+        let run = vec![
+            ("a.py".to_string(), entries(&[("tmp", "yourmom"), ("i", "yOurMom")])),
+            ("b.py".to_string(), entries(&[("tmp", "yourmom"), ("x", "YourMom")])),
+        ];
+        let map = RenameMap::from_run(run, true);
+        assert_eq!(map.global, entries(&[("tmp", "yourmom")]));
+        assert_eq!(map.files[0].1, entries(&[("i", "yOurMom")]));
+        assert_eq!(map.for_file(Path::new("b.py")), entries(&[("tmp", "yourmom"), ("x", "YourMom")]).into_iter().collect());
+    }
+}
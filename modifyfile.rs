@@ -1,15 +1,16 @@
 use std::fs::{File, OpenOptions};
 use std::io::{self, BufRead, BufReader, Write};
 
+#[allow(dead_code)]
 pub fn appendfile(file_path: &str, content: &str) -> io::Result<()> {
     let mut file = OpenOptions::new()
-        .write(true)
         .append(true)
         .open(file_path)?;
     writeln!(file, "{}", content)?;
     Ok(())
 }
 
+#[allow(dead_code)]
 pub fn prependfile(file_path: &str, content: &str) -> io::Result<()> {
     let og_content = read_file(file_path)?;
     let mut file = File::create(file_path)?;
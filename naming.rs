@@ -0,0 +1,205 @@
+use std::io;
+
+use regex::Regex;
+
+use crate::language_patterns::LanguagePattern;
+use crate::modifyfile;
+
+// The built-in themes, for --help and error messages
+pub const THEMES: &[&str] = &["yourmom", "nato", "greek", "animals", "pirate"];
+
+const YOURMOM: &[&str] = &[
+    "yourmom", "yOurMom", "YourMom", "yourMom", "YOURMOM",
+    "YouRmOm", "yOuRmOm", "YoUrMoM", "yourmOM", "YOURmom",
+    "YoUrMoThEr", "yourmother", "YourMother", "YOURMOTHER",
+    "yOuRmOtHeR", "yourmommy", "YourMommy", "YOURMOMMY",
+    "urmom", "UrMom", "URMOM", "yomama", "YoMama", "YOMAMA",
+];
+
+const NATO: &[&str] = &[
+    "alfa", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india",
+    "juliett", "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo",
+    "sierra", "tango", "uniform", "victor", "whiskey", "xray", "yankee", "zulu",
+];
+
+// "lambda" is a Python keyword, hence the older spelling
+const GREEK: &[&str] = &[
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+    "lamda", "mu", "nu", "xi", "omicron", "pi", "rho", "sigma", "tau", "upsilon", "phi",
+    "chi", "psi", "omega",
+];
+
+const ANIMALS: &[&str] = &[
+    "aardvark", "badger", "capybara", "dingo", "emu", "ferret", "gecko", "hedgehog", "ibis",
+    "jackal", "koala", "lemur", "meerkat", "narwhal", "ocelot", "penguin", "quokka",
+    "raccoon", "sloth", "tapir", "urchin", "vulture", "walrus", "xerus", "yak", "zebra",
+];
+
+const PIRATE: &[&str] = &[
+    "matey", "scallywag", "landlubber", "buccaneer", "shipmate", "bilge rat", "barnacle",
+    "doubloon", "cutlass", "parrot", "plank", "grog", "jolly roger", "powder monkey",
+    "sea dog", "first mate", "crows nest", "black spot", "shiver timbers", "booty",
+];
+
+// How multi-word names are joined for the language being rewritten
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Casing {
+    Snake,
+    Camel,
+}
+
+impl Casing {
+    pub fn for_language(language: &LanguagePattern) -> Self {
+        match language {
+            LanguagePattern::Rust | LanguagePattern::Python | LanguagePattern::Ruby => Casing::Snake,
+            _ => Casing::Camel,
+        }
+    }
+
+    // ["user", "account"] -> user_account / userAccount
+    pub fn apply(&self, words: &[String]) -> String {
+        match self {
+            Casing::Snake => words.join("_"),
+            Casing::Camel => {
+                let mut name = String::new();
+                for (i, word) in words.iter().enumerate() {
+                    if i == 0 {
+                        name.push_str(word);
+                    } else {
+                        let mut chars = word.chars();
+                        if let Some(first) = chars.next() {
+                            name.extend(first.to_uppercase());
+                            name.push_str(chars.as_str());
+                        }
+                    }
+                }
+                name
+            }
+        }
+    }
+}
+
+// Hands out replacement names. The index-th call must never repeat an earlier
+// one, however large the index gets.
+pub trait NameGenerator: Send + Sync {
+    fn generate(&self, index: usize, casing: Casing) -> String;
+}
+
+// The original theme: the casing *is* the joke, so it is left alone
+pub struct YourMomGenerator {
+    variations: Vec<String>,
+}
+
+impl NameGenerator for YourMomGenerator {
+    fn generate(&self, index: usize, _casing: Casing) -> String {
+        let n = self.variations.len();
+        let mut name = self.variations[index % n].clone();
+        // Past the end of the list, glue on another variation instead of repeating
+        let mut round = index / n;
+        while round > 0 {
+            name.push_str(&self.variations[(round - 1) % n]);
+            round /= n;
+        }
+        name
+    }
+}
+
+// Themes made of plain words or phrases, cased for the target language
+pub struct WordListGenerator {
+    words: Vec<Vec<String>>,
+}
+
+impl NameGenerator for WordListGenerator {
+    fn generate(&self, index: usize, casing: Casing) -> String {
+        let n = self.words.len();
+        let mut words = self.words[index % n].clone();
+        let mut round = index / n;
+        while round > 0 {
+            words.extend(self.words[(round - 1) % n].iter().cloned());
+            round /= n;
+        }
+        casing.apply(&words)
+    }
+}
+
+impl WordListGenerator {
+    fn new(entries: &[&str]) -> Self {
+        WordListGenerator {
+            words: entries
+                .iter()
+                .map(|entry| entry.split_whitespace().map(str::to_lowercase).collect())
+                .collect(),
+        }
+    }
+
+    // One word or phrase per line; blank lines and # comments are skipped
+    pub fn from_file(path: &str) -> io::Result<Self> {
+        let content = modifyfile::read_file(path)?;
+        let valid = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+        let mut entries = Vec::new();
+        for (line_no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if !line.split_whitespace().all(|word| valid.is_match(word)) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: '{}' can't be turned into an identifier", path, line_no + 1, line),
+                ));
+            }
+            entries.push(line);
+        }
+        if entries.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has no words in it", path)));
+        }
+        Ok(WordListGenerator::new(&entries))
+    }
+}
+
+// A built-in theme by name, or a word list file; `seed` shuffles the list
+pub fn theme(name: &str, seed: Option<u64>) -> io::Result<Box<dyn NameGenerator>> {
+    let builtin = match name {
+        "yourmom" => {
+            let mut variations: Vec<String> = YOURMOM.iter().map(|v| v.to_string()).collect();
+            if let Some(seed) = seed {
+                shuffle(&mut variations, seed);
+            }
+            return Ok(Box::new(YourMomGenerator { variations }));
+        }
+        "nato" => NATO,
+        "greek" => GREEK,
+        "animals" => ANIMALS,
+        "pirate" => PIRATE,
+        path => {
+            let mut generator = WordListGenerator::from_file(path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("theme '{}' is not one of {} and can't be read as a word list: {}", path, THEMES.join(", "), e),
+                )
+            })?;
+            if let Some(seed) = seed {
+                shuffle(&mut generator.words, seed);
+            }
+            return Ok(Box::new(generator));
+        }
+    };
+    let mut generator = WordListGenerator::new(builtin);
+    if let Some(seed) = seed {
+        shuffle(&mut generator.words, seed);
+    }
+    Ok(Box::new(generator))
+}
+
+// Fisher-Yates driven by splitmix64, so a given seed always yields the same order
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    for i in (1..items.len()).rev() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        items.swap(i, (z % (i as u64 + 1)) as usize);
+    }
+}
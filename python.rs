@@ -0,0 +1,476 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::language_patterns::{BindingKind, LanguagePattern};
+use crate::lexer::{self, Token, TokenKind};
+
+const PYTHON: &LanguagePattern = &LanguagePattern::Python;
+
+// Statements that end in `:` and may carry their body on the same line
+const COMPOUND: &[&str] = &["if", "elif", "else", "while", "for", "with", "try", "except", "finally", "async", "def", "class", "match", "case"];
+
+// One simple statement, or the header of a compound one, without its line breaks
+struct Statement<'a> {
+    tokens: Vec<Token<'a>>,
+    indent: usize,
+}
+
+// A `def` or `class` body, open until a statement is indented no deeper than its header
+struct Scope<'a> {
+    indent: usize,
+    class: bool,
+    // Names this function declared global or nonlocal: assigning them binds the outer name
+    outer: HashSet<&'a str>,
+}
+
+// Every name bound in content: assignment and `for` targets including tuple unpacking,
+// comprehension variables, walrus targets, every parameter of a `def` or `lambda`
+// (with `*args` and `**kwargs`), `with ... as` and `except ... as`. Assignments in a
+// class body define attributes rather than variables, and assignments to a name a
+// function declared `global` or `nonlocal` bind the outer variable, so neither counts,
+// and nothing is reported for a parameter the file passes by keyword (`f(x=1)`).
+pub(crate) fn bindings(content: &str) -> Vec<(usize, BindingKind, &str)> {
+    let tokens = code_tokens(content);
+    let passed: HashSet<(&str, &str)> = keyword_arguments(&tokens).into_iter().map(|(i, callee)| (callee, tokens[i].text)).collect();
+    // Parameters the file passes by keyword: renaming one anywhere would rename it everywhere and break the call
+    let mut keep: HashSet<&str> = HashSet::new();
+    let mut found = Vec::new();
+    let mut scopes: Vec<Scope> = Vec::new();
+
+    for statement in statements(content, &tokens) {
+        while scopes.last().is_some_and(|scope| statement.indent <= scope.indent) {
+            scopes.pop();
+        }
+        let tokens = &statement.tokens[..];
+        let mut bound = Vec::new();
+        nested_bindings(tokens, &mut bound);
+
+        let keyword = if tokens[0].is("async") { tokens.get(1) } else { tokens.first() };
+        match keyword.map(|token| token.text) {
+            Some("def") => {
+                if let Some(open) = tokens.iter().position(|token| token.is("(")) {
+                    let close = lexer::matching(tokens, open);
+                    parameters(&tokens[open + 1..close], BindingKind::Parameter, &mut bound);
+                    let function = tokens[open - 1].text;
+                    keep.extend(bound.iter().map(|(_, _, name)| *name).filter(|name| passed.contains(&(function, name))));
+                }
+            }
+            Some("global" | "nonlocal") => {
+                if let Some(scope) = scopes.last_mut().filter(|scope| !scope.class) {
+                    scope.outer.extend(tokens[1..].iter().filter(|token| token.kind == TokenKind::Ident).map(|token| token.text));
+                }
+            }
+            Some("with") => as_targets(tokens, BindingKind::Variable, &mut bound),
+            Some("except") => as_targets(tokens, BindingKind::ExceptionBinding, &mut bound),
+            Some("case") if !tokens.get(1).is_some_and(|token| token.is("=")) => as_targets(tokens, BindingKind::PatternBinding, &mut bound),
+            Some(word) if lexer::is_keyword(PYTHON, word) => {}
+            _ if scopes.last().is_some_and(|scope| scope.class) => {}
+            _ => assignment_targets(tokens, &mut bound),
+        }
+
+        let outer = scopes.last().map(|scope| &scope.outer);
+        found.extend(bound.into_iter().filter(|(_, kind, name)| {
+            !(matches!(kind, BindingKind::Variable | BindingKind::LoopIndex) && outer.is_some_and(|outer| outer.contains(name)))
+        }));
+
+        if let Some(header) = keyword.filter(|token| token.is("def") || token.is("class")) {
+            scopes.push(Scope { indent: statement.indent, class: header.is("class"), outer: HashSet::new() });
+        }
+    }
+    found.retain(|(_, _, name)| !keep.contains(name));
+    found
+}
+
+// Splits tokens into statements at line ends and `;` outside brackets. A compound
+// statement's header and the body on the same line are separate statements, the body
+// one level deeper.
+fn statements<'a>(content: &str, tokens: &[Token<'a>]) -> Vec<Statement<'a>> {
+    let mut found = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut indent = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.saturating_sub(1);
+        }
+        let ends_line = token.kind == TokenKind::Newline && depth == 0;
+        if !(ends_line || (token.is(";") && depth == 0) || i + 1 == tokens.len()) {
+            continue;
+        }
+        let end = if ends_line || token.is(";") { i } else { i + 1 };
+        let mut statement: Vec<Token> = tokens[start..end].iter().copied().filter(|token| token.kind != TokenKind::Newline).collect();
+        if let Some(first) = statement.first() {
+            if start == 0 || tokens[start - 1].kind == TokenKind::Newline {
+                indent = first.offset - content[..first.offset].rfind('\n').map_or(0, |i| i + 1);
+            }
+            match header_colon(&statement) {
+                Some(colon) if colon + 1 < statement.len() => {
+                    let body = statement.split_off(colon + 1);
+                    found.push(Statement { tokens: statement, indent });
+                    found.push(Statement { tokens: body, indent: indent + 1 });
+                }
+                _ => found.push(Statement { tokens: statement, indent }),
+            }
+        }
+        start = i + 1;
+    }
+    found
+}
+
+// The `:` ending a compound statement's header
+fn header_colon(tokens: &[Token]) -> Option<usize> {
+    if !tokens.first().is_some_and(|token| COMPOUND.contains(&token.text)) {
+        return None;
+    }
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.saturating_sub(1);
+        } else if token.is("lambda") && depth == 0 {
+            // Its `:` isn't the header's; a lambda at the top of a header is rare enough to give up on
+            return None;
+        } else if token.is(":") && depth == 0 {
+            return Some(i);
+        }
+    }
+    None
+}
+
+// Bindings that can appear inside any expression: walrus targets, lambda parameters
+// and `for` targets, which are loop indexes at the start of a statement and
+// comprehension variables anywhere else
+fn nested_bindings<'a>(tokens: &[Token<'a>], found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is(":=") && i > 0 && tokens[i - 1].kind == TokenKind::Ident {
+            found.push((tokens[i - 1].offset, BindingKind::Variable, tokens[i - 1].text));
+        } else if token.is("lambda") {
+            let colon = lexer::top_level(&tokens[i + 1..], |token| token.is(":")).map_or(i + 1, |colon| i + 1 + colon);
+            parameters(&tokens[i + 1..colon], BindingKind::LambdaParam, found);
+        } else if token.is("for") {
+            let Some(end) = lexer::top_level(&tokens[i + 1..], |token| token.is("in")) else {
+                continue;
+            };
+            let loop_header = i == 0 || (i == 1 && tokens[0].is("async"));
+            let kind = if loop_header { BindingKind::LoopIndex } else { BindingKind::Comprehension };
+            targets(&tokens[i + 1..i + 1 + end], kind, found);
+        }
+    }
+}
+
+// The names in a parameter list, without their annotations and defaults
+fn parameters<'a>(tokens: &[Token<'a>], kind: BindingKind, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    let mut depth = 0usize;
+    let mut expecting = true;
+    for token in tokens {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && token.is(",") {
+            expecting = true;
+        } else if expecting && (token.is("*") || token.is("**") || token.is("/")) {
+            // *args, **kwargs and the bare * and / markers
+        } else if expecting {
+            if token.kind == TokenKind::Ident && !lexer::is_keyword(PYTHON, token.text) {
+                found.push((token.offset, kind, token.text));
+            }
+            expecting = false;
+        }
+    }
+}
+
+// `a = b = f()` binds a and b, `x: int = 0` binds x; `obj.attr = ...` and `d[k] = ...` bind nothing
+fn assignment_targets<'a>(tokens: &[Token<'a>], found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    if tokens.len() > 1 && tokens[0].kind == TokenKind::Ident && tokens[1].is(":") {
+        found.push((tokens[0].offset, BindingKind::Variable, tokens[0].text));
+        return;
+    }
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.saturating_sub(1);
+        } else if token.is("=") && depth == 0 {
+            targets(&tokens[start..i], BindingKind::Variable, found);
+            start = i + 1;
+        }
+    }
+}
+
+// The names after each `as`: `with open(p) as f, lock as (a, b):`
+fn as_targets<'a>(tokens: &[Token<'a>], kind: BindingKind, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    for (i, token) in tokens.iter().enumerate() {
+        if !token.is("as") || i + 1 >= tokens.len() {
+            continue;
+        }
+        let end = if tokens[i + 1].opens() { lexer::matching(tokens, i + 1) + 1 } else { i + 2 };
+        targets(&tokens[i + 1..end.min(tokens.len())], kind, found);
+    }
+}
+
+// The names a target list binds: `a`, `a, *rest`, `(a, [b, c])`. Attributes,
+// subscripts and anything inside a call are not bound.
+fn targets<'a>(tokens: &[Token<'a>], kind: BindingKind, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    // How deep into a subscript or call we are
+    let mut access = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        if token.opens() {
+            if access > 0 || previous.is_some_and(is_callee) {
+                access += 1;
+            }
+        } else if token.closes() {
+            access = access.saturating_sub(1);
+        } else if access == 0
+            && token.kind == TokenKind::Ident
+            && !lexer::is_keyword(PYTHON, token.text)
+            && !previous.is_some_and(|previous| previous.is("."))
+            && !tokens.get(i + 1).is_some_and(|next| next.is(".") || next.is("(") || next.is("["))
+        {
+            found.push((token.offset, kind, token.text));
+        }
+    }
+}
+
+// Renames every use of each old name in code and in f-string expressions, leaving
+// alone attributes (`obj.x`), keyword arguments (`f(x=1)`), the names an `import`
+// brings in, comments and plain strings
+pub(crate) fn rename(content: &str, replacements: &[(String, String)]) -> String {
+    let names: HashMap<&str, &str> = replacements.iter().map(|(old, new)| (old.as_str(), new.as_str())).collect();
+    let mut edits = Vec::new();
+    rename_in(content, 0, &names, &mut edits);
+    lexer::apply_edits(content, edits)
+}
+
+fn rename_in(code: &str, base: usize, names: &HashMap<&str, &str>, edits: &mut Vec<(usize, usize, String)>) {
+    let tokens = code_tokens(code);
+    let keywords: HashSet<usize> = keyword_arguments(&tokens).into_iter().map(|(i, _)| i).collect();
+    let mut importing = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        match token.kind {
+            TokenKind::Newline => importing = false,
+            TokenKind::Punct if token.is(";") => importing = false,
+            TokenKind::Str => {
+                for (start, end) in fstring_expressions(token.text) {
+                    let offset = token.offset + start;
+                    rename_in(&code[offset..token.offset + end], base + offset, names, edits);
+                }
+            }
+            TokenKind::Ident => {
+                let line_start = previous.is_none_or(|previous| previous.kind == TokenKind::Newline || previous.is(";"));
+                if line_start && (token.is("import") || token.is("from")) {
+                    importing = true;
+                    continue;
+                }
+                let Some(new_name) = names.get(token.text) else {
+                    continue;
+                };
+                let attribute = previous.is_some_and(|previous| previous.is("."));
+                let imported = importing && !previous.is_some_and(|previous| previous.is("as"));
+                if !(attribute || imported || keywords.contains(&i)) {
+                    edits.push((base + token.offset, token.text.len(), new_name.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// code's tokens without comments, and without the line breaks inside brackets
+fn code_tokens(code: &str) -> Vec<Token<'_>> {
+    let mut depth = 0usize;
+    lexer::tokenize(code, PYTHON)
+        .into_iter()
+        .filter(|token| {
+            if token.opens() {
+                depth += 1;
+            } else if token.closes() {
+                depth = depth.saturating_sub(1);
+            }
+            token.kind != TokenKind::Comment && (token.kind != TokenKind::Newline || depth == 0)
+        })
+        .collect()
+}
+
+// The index of each keyword argument's name (the x in `f(x=1)`), with the name of
+// the function it's passed to
+fn keyword_arguments<'a>(tokens: &[Token<'a>]) -> Vec<(usize, &'a str)> {
+    // For each open bracket, the function it calls, if it's a call
+    let mut calls: Vec<Option<&str>> = Vec::new();
+    let mut found = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        if token.opens() {
+            let definition = i >= 2 && tokens[i - 2].is("def");
+            let callee = previous.filter(|previous| token.is("(") && is_callee(previous) && !definition);
+            calls.push(callee.map(|callee| callee.text));
+        } else if token.closes() {
+            calls.pop();
+        } else if let Some(Some(callee)) = calls.last() {
+            let argument_start = previous.is_some_and(|previous| previous.is("(") || previous.is(","));
+            if token.kind == TokenKind::Ident && argument_start && tokens.get(i + 1).is_some_and(|next| next.is("=")) {
+                found.push((i, *callee));
+            }
+        }
+    }
+    found
+}
+
+// Byte ranges of the expressions inside an f-string literal's braces, including
+// those nested in a format spec: `{x}`, `{x!r}`, `{x:>{width}}`, `{x=}`
+fn fstring_expressions(literal: &str) -> Vec<(usize, usize)> {
+    let quote = literal.find(['"', '\'']).unwrap_or(0);
+    if !literal[..quote].contains(['f', 'F']) {
+        return Vec::new();
+    }
+    let bytes = literal.as_bytes();
+    let mut found = Vec::new();
+    let mut i = quote;
+    while i < bytes.len() {
+        if bytes[i] == b'{' && bytes.get(i + 1) == Some(&b'{') {
+            i += 2;
+        } else if bytes[i] == b'{' {
+            let end = expression_end(literal, i + 1);
+            found.push((i + 1, end));
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+// Where the expression starting at start ends: at its closing brace, or at a
+// conversion, format spec or `=` outside any brackets or strings
+fn expression_end(literal: &str, start: usize) -> usize {
+    let bytes = literal.as_bytes();
+    let mut depth = 0usize;
+    let mut quote = None;
+    for i in start..bytes.len() {
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied().unwrap_or(0);
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            b'"' | b'\'' => quote = Some(c),
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' => depth = depth.saturating_sub(1),
+            b'}' if depth > 0 => depth -= 1,
+            b'}' => return i,
+            b'!' if depth == 0 && next != b'=' => return i,
+            b':' if depth == 0 => return i,
+            b'=' if depth == 0 && matches!(next, b'}' | b'!' | b':') && !matches!(bytes[i - 1], b'=' | b'!' | b'<' | b'>') => return i,
+            _ => {}
+        }
+    }
+    bytes.len()
+}
+
+// Whether a bracket after token is a call or subscript rather than a tuple or list
+fn is_callee(token: &Token) -> bool {
+    (token.kind == TokenKind::Ident && !lexer::is_keyword(PYTHON, token.text)) || token.is(")") || token.is("]") || token.kind == TokenKind::Str
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bound(content: &str) -> Vec<(usize, BindingKind, &str)> {
+        let mut found = bindings(content);
+        found.sort_by_key(|(offset, _, _)| *offset);
+        found
+    }
+
+    fn renamed(content: &str, old: &str, new: &str) -> String {
+        rename(content, &[(old.to_string(), new.to_string())])
+    }
+
+    #[test]
+    fn assignments_loops_and_parameters() {
+        let content = "def f(a, *args, b: int = 0, **kw):\n    for i, (j, k) in a:\n        x = y = i\n    return x\n";
+        assert_eq!(
+            bound(content),
+            [
+                (6, BindingKind::Parameter, "a"),
+                (10, BindingKind::Parameter, "args"),
+                (16, BindingKind::Parameter, "b"),
+                (30, BindingKind::Parameter, "kw"),
+                (43, BindingKind::LoopIndex, "i"),
+                (47, BindingKind::LoopIndex, "j"),
+                (50, BindingKind::LoopIndex, "k"),
+                (67, BindingKind::Variable, "x"),
+                (71, BindingKind::Variable, "y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comprehensions_lambdas_and_as_targets() {
+        let content = "ys = [v for v in xs if (n := v)]\nf = lambda p: p\nwith open(q) as fh:\n    pass\ntry:\n    pass\nexcept E as err:\n    pass\n";
+        assert_eq!(
+            bound(content),
+            [
+                (0, BindingKind::Variable, "ys"),
+                (12, BindingKind::Comprehension, "v"),
+                (24, BindingKind::Variable, "n"),
+                (33, BindingKind::Variable, "f"),
+                (44, BindingKind::LambdaParam, "p"),
+                (65, BindingKind::Variable, "fh"),
+                (104, BindingKind::ExceptionBinding, "err"),
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_class_fields_and_outer_names_are_not_bound() {
+        let content = "class C:\n    tmp = 1\n\ndef f():\n    global g\n    g = 1\n    self.x = 2\n    d[k] = 3\n";
+        assert!(bound(content).is_empty(), "{:?}", bound(content));
+
+        let nested = "def outer():\n    n = 0\n    def inner():\n        nonlocal n\n        n = 1\n";
+        assert_eq!(bound(nested), [(17, BindingKind::Variable, "n")]);
+    }
+
+    #[test]
+    fn parameters_passed_by_keyword_are_kept() {
+        let content = "def f(tmp, x):\n    return tmp + x\n\nf(tmp=1, x=2)\n";
+        assert!(bound(content).is_empty(), "{:?}", bound(content));
+    }
+
+    #[test]
+    fn rename_leaves_attributes_keywords_and_strings_alone() {
+        let content = "tmp = obj.tmp\ng(tmp=tmp)  # tmp\nprint(\"tmp\", tmp)\n";
+        assert_eq!(renamed(content, "tmp", "total"), "total = obj.tmp\ng(tmp=total)  # tmp\nprint(\"tmp\", total)\n");
+    }
+
+    #[test]
+    fn rename_reaches_into_f_strings() {
+        let content = "x = 1\nprint(f\"{x!r} {x:>{x}} {x=} x {{x}}\", 'x')\n";
+        assert_eq!(renamed(content, "x", "width"), "width = 1\nprint(f\"{width!r} {width:>{width}} {width=} x {{x}}\", 'x')\n");
+    }
+
+    #[test]
+    fn rename_covers_comprehensions_and_global_statements() {
+        let content = "tmp = 0\ndef f():\n    global tmp\n    tmp = [tmp for tmp in range(3)]\n";
+        assert_eq!(renamed(content, "tmp", "total"), "total = 0\ndef f():\n    global total\n    total = [total for total in range(3)]\n");
+    }
+
+    #[test]
+    fn every_prefix_of_a_file_being_typed_is_handled() {
+        let content = "import os\n\n@cache\nasync def load(path: str, *args, mode=\"r\", **kw) -> list:\n    global seen\n    with open(path, mode) as (fh):\n        data = [line.strip() for line in fh if (n := len(line))]\n    try:\n        tmp = {k: v for k, v in kw.items()}\n    except (OSError, ValueError) as err:\n        raise err\n    f = lambda x, y=1: x + y\n    print(f\"{data!r:>{n}} {tmp=}\", sep=\"\")\n    return [tmp, f(1), load(path=path)]\n\nclass A:\n    x = 1\n    def m(self, i): self.x = i; return i\n";
+        let replacements = [("tmp".to_string(), "total".to_string()), ("x".to_string(), "width".to_string())];
+        for end in (0..=content.len()).filter(|&end| content.is_char_boundary(end)) {
+            bindings(&content[..end]);
+            rename(&content[..end], &replacements);
+        }
+    }
+}
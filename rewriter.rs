@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::language_patterns::LanguagePattern;
+use crate::mapfile::RenameMap;
+use crate::naming::{Casing, NameGenerator};
+use crate::rules::{self, Score};
+use crate::{java, javascript, kotlin, python, rust};
+use crate::scanner::{filtered_declarations, first_declarations, FileFilter, Scanner};
+use crate::suggest;
+use crate::Warning;
+
+// Which bad name got which replacement; shared between files when running in project mode
+struct NameMapping {
+    names: HashMap<String, String>,
+    generator: Arc<dyn NameGenerator>,
+    strategy: NamingStrategy,
+    // How many names the generator has handed out so far
+    generated: usize,
+    // Loaded from --apply-map: only rename what the map lists, never invent names
+    locked: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NamingStrategy {
+    #[default]
+    YourMom,
+    // Derive a real name from context, falling back to YourMom when there's nothing to go on
+    Suggest,
+}
+
+impl NamingStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "yourmom" => Some(NamingStrategy::YourMom),
+            "suggest" => Some(NamingStrategy::Suggest),
+            _ => None,
+        }
+    }
+}
+
+impl NameMapping {
+    fn new(generator: Arc<dyn NameGenerator>, strategy: NamingStrategy) -> Self {
+        NameMapping { names: HashMap::new(), generator, strategy, generated: 0, locked: false }
+    }
+
+    // is_taken rejects names that would clash with the file being rewritten, including
+    // ones handed out for earlier files
+    fn replacement_for(&mut self, var_name: &str, suggestion: Option<String>, casing: Casing, is_taken: impl Fn(&str) -> bool) -> Option<String> {
+        if self.locked {
+            return self.names.get(var_name).cloned();
+        }
+        if let Some(existing) = self.names.get(var_name) {
+            if !is_taken(existing) {
+                return Some(existing.clone());
+            }
+            // This file already uses the shared name: it gets one of its own, and the
+            // files after it keep getting the shared one
+            return Some(self.generate(casing, &is_taken));
+        }
+        let replacement = match suggestion {
+            Some(name) => name,
+            None => self.generate(casing, &is_taken),
+        };
+        self.names.insert(var_name.to_string(), replacement.clone());
+        Some(replacement)
+    }
+
+    fn generate(&mut self, casing: Casing, is_taken: impl Fn(&str) -> bool) -> String {
+        loop {
+            let name = self.generator.generate(self.generated, casing);
+            self.generated += 1;
+            if !is_taken(&name) {
+                return name;
+            }
+        }
+    }
+}
+
+// How far a generated name reaches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MappingScope {
+    // Every file starts from a clean slate
+    File,
+    // A bad name gets the same replacement in every file
+    Project,
+    // Like Project, with a separate mapping for each language
+    Language,
+}
+
+// What a rewrite or restore produced: the new content, the (from, to) renames made
+// in order, and whatever was left alone on the way
+#[derive(Debug, Clone, Default)]
+pub struct Rewrite {
+    pub content: String,
+    pub replacements: Vec<(String, String)>,
+    pub warnings: Vec<Warning>,
+    // Each renamed name a rule flags, scored where it was first declared; a restore scores nothing
+    pub scores: HashMap<String, Score>,
+}
+
+// Renames bad names in source text, remembering the names it handed out
+// across files as far as its MappingScope reaches
+pub struct Rewriter {
+    scope: MappingScope,
+    generator: Arc<dyn NameGenerator>,
+    strategy: NamingStrategy,
+    mappings: HashMap<Option<LanguagePattern>, NameMapping>,
+    applied: Option<RenameMap>,
+}
+
+impl Rewriter {
+    // With `applied`, only the renames in the map are made and no names are generated
+    pub fn new(scope: MappingScope, generator: Box<dyn NameGenerator>, strategy: NamingStrategy, applied: Option<RenameMap>) -> Self {
+        Rewriter { scope, generator: Arc::from(generator), strategy, mappings: HashMap::new(), applied }
+    }
+
+    // The rewritten content and the (old, new) renames made, in order of first declaration
+    pub fn rewrite(&mut self, file_path: &Path, content: &str, language: &LanguagePattern, scanner: &Scanner) -> Rewrite {
+        let filter = scanner.for_file(file_path);
+        extract_and_replace_variables(content, language, self.for_file(file_path, language), &filter)
+    }
+
+    // Each old name becomes its new name: every whole-word occurrence, except in languages
+    // with their own replacement policy (Python, Rust, JavaScript, Java, C#, Kotlin) that leave fields, strings and the like alone
+    pub fn apply(content: &str, language: &LanguagePattern, replacements: &[(String, String)]) -> String {
+        apply_replacements(content, language, replacements)
+    }
+
+    fn for_file(&mut self, file_path: &Path, language: &LanguagePattern) -> &mut NameMapping {
+        if let Some(map) = &self.applied {
+            let names = map.for_file(file_path);
+            self.mappings.clear();
+            let generator = self.generator.clone();
+            return self.mappings.entry(None).or_insert(NameMapping { names, locked: true, ..NameMapping::new(generator, self.strategy) });
+        }
+        let key = match self.scope {
+            MappingScope::File => {
+                self.mappings.clear();
+                None
+            }
+            MappingScope::Project => None,
+            MappingScope::Language => Some(language.clone()),
+        };
+        let (generator, strategy) = (&self.generator, self.strategy);
+        self.mappings.entry(key).or_insert_with(|| NameMapping::new(generator.clone(), strategy))
+    }
+}
+
+fn extract_and_replace_variables(content: &str, language: &LanguagePattern, mapping: &mut NameMapping, filter: &FileFilter) -> Rewrite {
+    // First pass: identify all bad variables
+    let (found, mut warnings) = filtered_declarations(content, language, filter);
+    
+    // Hand out names in order of first appearance so the result doesn't depend on pattern order
+    let mut replacements: Vec<(String, String)> = Vec::new();
+    let mut declared = Vec::new();
+    for (offset, var_name) in first_declarations(&found) {
+        if !filter.keeps_finding(content, offset, var_name) {
+            continue;
+        }
+        let suggestion = match mapping.strategy {
+            // A derived name like `person` usually also names a parameter somewhere else in the
+            // file, so only the lines where var_name is live have to be free of it
+            NamingStrategy::Suggest => suggest::suggest_name(content, offset, var_name, language)
+                .filter(|name| find_collision(usage_span(content, var_name), name, &replacements).is_none()),
+            NamingStrategy::YourMom => None,
+        };
+        let taken = |name: &str| find_collision(content, name, &replacements).is_some();
+        let Some(replacement) = mapping.replacement_for(var_name, suggestion, Casing::for_language(language), taken) else {
+            continue;
+        };
+        if mapping.locked {
+            // Hand-edited names may clash with something already in the file
+            if let Some(reason) = find_collision(content, &replacement, &replacements) {
+                warnings.push(Warning::Rejected { old: var_name.to_string(), new: replacement, reason });
+                continue;
+            }
+        }
+        replacements.push((var_name.to_string(), replacement));
+        declared.push((offset, var_name));
+    }
+    
+    // Second pass: replace all occurrences
+    let result = apply_replacements(content, language, &replacements);
+    let scores = declared
+        .iter()
+        .zip(rules::score_declarations(&declared, content, language))
+        .filter_map(|((_, var_name), score)| Some((var_name.to_string(), score?)))
+        .collect();
+    Rewrite { content: result, replacements, warnings, scores }
+}
+
+fn apply_replacements(content: &str, language: &LanguagePattern, replacements: &[(String, String)]) -> String {
+    // Nothing to rename is the common case, and not worth tokenizing for
+    if replacements.is_empty() {
+        return content.to_string();
+    }
+    match language {
+        LanguagePattern::Python => return python::rename(content, replacements),
+        LanguagePattern::Rust => return rust::rename(content, replacements),
+        LanguagePattern::JavaScript => return javascript::rename(content, replacements),
+        LanguagePattern::Java | LanguagePattern::CSharp => return java::rename(content, language, replacements),
+        LanguagePattern::Kotlin => return kotlin::rename(content, replacements),
+        _ => {}
+    }
+    let mut result = content.to_string();
+    for (old_name, new_name) in replacements {
+        // Create a regex that matches the variable name with word boundaries
+        let boundary_pattern = format!(r"\b{}\b", regex::escape(old_name));
+        
+        if let Ok(re) = Regex::new(&boundary_pattern) {
+            result = re.replace_all(&result, new_name.as_str()).to_string();
+        }
+    }
+    
+    result
+}
+
+// The lines from the first to the last occurrence of var_name
+fn usage_span<'a>(content: &'a str, var_name: &str) -> &'a str {
+    let Ok(re) = Regex::new(&format!(r"\b{}\b", regex::escape(var_name))) else {
+        return content;
+    };
+    let (Some(first), Some(last)) = (re.find(content), re.find_iter(content).last()) else {
+        return content;
+    };
+    let start = content[..first.start()].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[last.end()..].find('\n').map_or(content.len(), |i| last.end() + i);
+    &content[start..end]
+}
+
+pub fn find_collision(content: &str, new_name: &str, accepted: &[(String, String)]) -> Option<String> {
+    if let Some((other, _)) = accepted.iter().find(|(_, new)| new == new_name) {
+        return Some(format!("{} is already being renamed to it", other));
+    }
+    let re = Regex::new(&format!(r"\b{}\b", regex::escape(new_name))).ok()?;
+    if re.is_match(content) {
+        return Some("an identifier with that name already exists in the file".to_string());
+    }
+    None
+}
+
+// Undo a previous run: every replacement still present in the code goes back to its original name
+pub fn restore_variables(content: &str, language: &LanguagePattern, names: &HashMap<String, String>) -> Rewrite {
+    let mut originals: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (old, new) in names {
+        originals.entry(new.as_str()).or_default().push(old.as_str());
+    }
+    for candidates in originals.values_mut() {
+        candidates.sort();
+    }
+    let mut renamed: Vec<&str> = originals.keys().copied().collect();
+    renamed.sort();
+    
+    let mut result = content.to_string();
+    let mut restored = Vec::new();
+    let mut warnings = Vec::new();
+    for new_name in renamed {
+        if first_reference(&result, language, new_name).is_none() {
+            continue;
+        }
+        let candidates = &originals[new_name];
+        if candidates.len() > 1 {
+            warnings.push(Warning::Ambiguous { name: new_name.to_string(), candidates: candidates.iter().map(|name| name.to_string()).collect() });
+            continue;
+        }
+        let old_name = candidates[0];
+        if let Some(line) = first_reference(&result, language, old_name) {
+            warnings.push(Warning::NotRestored { new: new_name.to_string(), old: old_name.to_string(), line });
+            continue;
+        }
+        let replacement = [(new_name.to_string(), old_name.to_string())];
+        result = apply_replacements(&result, language, &replacement);
+        restored.extend(replacement);
+    }
+    
+    Rewrite { content: result, replacements: restored, warnings, scores: HashMap::new() }
+}
+
+// The first line where the language's rename would touch name, i.e. where it's a variable and
+// not a field, keyword argument or lifetime that merely looks the same
+fn first_reference(content: &str, language: &LanguagePattern, name: &str) -> Option<usize> {
+    let probed = apply_replacements(content, language, &[(name.to_string(), "badvars_probe".to_string())]);
+    content.lines().zip(probed.lines()).position(|(before, after)| before != after).map(|index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naming;
+
+    #[test]
+    fn shared_names_do_not_clash_with_a_later_file() {
+        let mut rewriter = Rewriter::new(MappingScope::Project, naming::theme("nato", None).unwrap(), NamingStrategy::YourMom, None);
+        let scanner = Scanner::default();
+        let first = rewriter.rewrite(Path::new("a.py"), "tmp = 1\nprint(tmp)\n", &LanguagePattern::Python, &scanner).replacements;
+        assert_eq!(first, [("tmp".to_string(), "alfa".to_string())]);
+
+        let second = rewriter.rewrite(Path::new("b.py"), "def f(alfa):\n    tmp = alfa\n    return tmp\n", &LanguagePattern::Python, &scanner);
+        assert_eq!(second.replacements, [("tmp".to_string(), "bravo".to_string())]);
+        assert_eq!(second.content, "def f(alfa):\n    bravo = alfa\n    return bravo\n");
+
+        // Files without the clash keep getting the shared name
+        let third = rewriter.rewrite(Path::new("c.py"), "tmp = 2\n", &LanguagePattern::Python, &scanner).replacements;
+        assert_eq!(third, [("tmp".to_string(), "alfa".to_string())]);
+    }
+
+    #[test]
+    fn emitted_map_restores_every_file() {
+        // Fields, keyword arguments and lifetimes that share an original name don't block the restore
+        let originals = [
+            ("a.py", LanguagePattern::Python, "tmp = 1\nprint(tmp)\n"),
+            ("b.py", LanguagePattern::Python, "x = 2\nprint(x)\n"),
+            ("c.py", LanguagePattern::Python, "tmp = 3\nx = tmp\n"),
+            ("d.py", LanguagePattern::Python, "class A:\n    def f(self):\n        tmp = 1\n        self.tmp = g(tmp=tmp)\n"),
+            ("e.rs", LanguagePattern::Rust, "struct P { x: i32 }\nfn f<'a>(s: &'a str) -> P {\n    let a = s.len() as i32;\n    let x = a;\n    P { x: x }\n}\n"),
+        ];
+        for scope in [MappingScope::File, MappingScope::Project] {
+            let mut rewriter = Rewriter::new(scope, naming::theme("yourmom", None).unwrap(), NamingStrategy::YourMom, None);
+            let scanner = Scanner::default();
+            let mut rewritten = Vec::new();
+            let mut run = Vec::new();
+            for (path, language, content) in &originals {
+                let rewrite = rewriter.rewrite(Path::new(path), content, language, &scanner);
+                rewritten.push(rewrite.content);
+                run.push((path.to_string(), rewrite.replacements));
+            }
+            let map = RenameMap::from_run(run, scope != MappingScope::File);
+            for ((path, language, original), result) in originals.iter().zip(&rewritten) {
+                assert_ne!(result, original);
+                let restored = restore_variables(result, language, &map.for_file(Path::new(path)));
+                assert!(restored.warnings.is_empty(), "{:?} in {} in {:?} scope", restored.warnings, path, scope);
+                assert_eq!(restored.content, *original, "{} in {:?} scope", path, scope);
+            }
+        }
+    }
+
+    #[test]
+    fn restore_returns_what_it_left_alone() {
+        let names: HashMap<String, String> = [("x", "alfa"), ("tmp", "alfa"), ("foo", "bravo")].iter().map(|(old, new)| (old.to_string(), new.to_string())).collect();
+        let restore = restore_variables("alfa = 1\nbravo = alfa\nfoo = 2\n", &LanguagePattern::Python, &names);
+        assert!(restore.replacements.is_empty());
+        assert_eq!(
+            restore.warnings,
+            [
+                Warning::Ambiguous { name: "alfa".to_string(), candidates: vec!["tmp".to_string(), "x".to_string()] },
+                Warning::NotRestored { new: "bravo".to_string(), old: "foo".to_string(), line: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_names_come_with_their_scores() {
+        let mut rewriter = Rewriter::new(MappingScope::File, naming::theme("nato", None).unwrap(), NamingStrategy::YourMom, None);
+        let scanner = Scanner::default();
+        let content = "def f():\n    tmp = 1\n    d = tmp\n    return d\n";
+        let rewrite = rewriter.rewrite(Path::new("a.py"), content, &LanguagePattern::Python, &scanner);
+        for (old, _) in &rewrite.replacements {
+            let offset = content.find(&format!("{} =", old)).unwrap();
+            assert_eq!(rewrite.scores[old].total, rules::score_declaration(old, offset, content, &LanguagePattern::Python).unwrap().total);
+        }
+        assert_eq!(rewrite.scores.len(), 2);
+
+        let clean = rewriter.rewrite(Path::new("b.py"), "count = 1\n", &LanguagePattern::Python, &scanner);
+        assert!(clean.replacements.is_empty() && clean.scores.is_empty());
+        assert_eq!(clean.content, "count = 1\n");
+    }
+
+    #[test]
+    fn unused_suppressions_come_back_as_warnings() {
+        let mut rewriter = Rewriter::new(MappingScope::File, naming::theme("nato", None).unwrap(), NamingStrategy::YourMom, None);
+        let scanner = Scanner::default().with_unused_suppressions(true);
+        let rewrite = rewriter.rewrite(Path::new("a.py"), "count = 1  # badvars: ignore\n", &LanguagePattern::Python, &scanner);
+        assert_eq!(rewrite.warnings, [Warning::UnusedSuppression { line: 1, comment: "# badvars: ignore".to_string() }]);
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt;
+
+use regex::Regex;
+
+use crate::language_patterns::{BindingKind, LanguagePattern};
+use crate::lexer::{self, Token, TokenKind};
+
+// How forgiving to be about short names in tight scopes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RuleProfile {
+    // Every bad name is flagged, wherever it is bound
+    #[default]
+    Strict,
+    // Names most style guides accept where they're bound: `i` as a loop index,
+    // `e` for a caught exception, `x` as a lambda or comprehension variable.
+    // The same names at function scope are still flagged.
+    Idiomatic,
+}
+
+impl RuleProfile {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "strict" => Some(RuleProfile::Strict),
+            "idiomatic" => Some(RuleProfile::Idiomatic),
+            _ => None,
+        }
+    }
+
+    pub fn allows(&self, kind: BindingKind, var_name: &str) -> bool {
+        if *self == RuleProfile::Strict {
+            return false;
+        }
+        match kind {
+            BindingKind::LoopIndex => matches!(var_name, "i" | "j" | "k" | "n" | "x" | "y" | "z"),
+            BindingKind::ExceptionBinding => {
+                matches!(var_name, "e" | "ex" | "exc" | "err" | "error")
+                    || var_name.strip_prefix("err").is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+            }
+            BindingKind::LambdaParam | BindingKind::Comprehension => var_name.len() == 1,
+            BindingKind::Variable | BindingKind::Parameter | BindingKind::PatternBinding => false,
+        }
+    }
+}
+
+// Name of the rule that flags var_name, if any
+pub fn bad_variable_rule(var_name: &str) -> Option<&'static str> {
+    // Skip if it's already a yourmom variation
+    if var_name.to_lowercase().contains("yourmom") || 
+       var_name.to_lowercase().contains("yourmother") ||
+       var_name.to_lowercase().contains("urmom") ||
+       var_name.to_lowercase().contains("yomama") {
+        return None;
+    }
+
+    let bad_patterns = [
+        // Single letter variables (a-z, but keep common ones like _ or $)
+        ("single letter", Regex::new(r"^[a-z]$").unwrap()),
+        // Common bad patterns
+        ("single letter", Regex::new(r"^(i|j|k|l|m|n|x|y|z|a|b|c|d|e|f|g|h)$").unwrap()),
+        // Generic terrible names
+        ("generic name", Regex::new(r"^(temp|tmp|var|val|data|item|elem|node|obj|thing|stuff)$").unwrap()),
+        // Variables with just numbers
+        ("numbered name", Regex::new(r"^[a-z]+\d+$").unwrap()),
+        // Really short meaningless names
+        ("doubled letter", Regex::new(r"^(aa|bb|cc|dd|ee|ff|gg|hh|ii|jj|kk|ll|mm|nn|oo|pp|qq|rr|ss|tt|uu|vv|ww|xx|yy|zz)$").unwrap()),
+    ];
+    
+    bad_patterns.iter().find(|(_, pattern)| pattern.is_match(var_name)).map(|(rule, _)| *rule)
+}
+
+// How bad a flagged name is, and why. Higher is worse.
+#[derive(Debug, Clone)]
+pub struct Score {
+    pub total: u32,
+    pub factors: Vec<(&'static str, u32)>,
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let factors: Vec<String> = self
+            .factors
+            .iter()
+            .filter(|(_, points)| *points > 0)
+            .map(|(factor, points)| format!("{} +{}", factor, points))
+            .collect();
+        write!(f, "score {}: {}", self.total, factors.join(", "))
+    }
+}
+
+// Scores a name flagged by bad_variable_rule at its first declaration in content;
+// None if no rule flags it
+pub fn score_variable(var_name: &str, content: &str, language: &LanguagePattern) -> Option<Score> {
+    let offset = language
+        .find_bindings(content)
+        .into_iter()
+        .filter(|(_, _, name)| *name == var_name)
+        .map(|(offset, _, _)| offset)
+        .min()
+        .unwrap_or(0);
+    score_declaration(var_name, offset, content, language)
+}
+
+// Scores the name declared at offset. A `tmp` read once on the next line scores low;
+// a `d` threaded through a 300-line function scores high. Only uses in code within
+// the declaration's scope count, so neither a string that mentions the name nor a
+// namesake in another function makes it look worse.
+pub(crate) fn score_declaration(var_name: &str, offset: usize, content: &str, language: &LanguagePattern) -> Option<Score> {
+    score_declarations(&[(offset, var_name)], content, language).pop().flatten()
+}
+
+// score_declaration for each (offset, name), tokenizing content once for all of them
+pub(crate) fn score_declarations(declarations: &[(usize, &str)], content: &str, language: &LanguagePattern) -> Vec<Option<Score>> {
+    if declarations.iter().all(|(_, var_name)| bad_variable_rule(var_name).is_none()) {
+        return vec![None; declarations.len()];
+    }
+    let tokens: Vec<Token> = lexer::tokenize(content, language)
+        .into_iter()
+        .filter(|token| !matches!(token.kind, TokenKind::Comment | TokenKind::Newline))
+        .collect();
+    declarations.iter().map(|&(offset, var_name)| score_in(&tokens, var_name, offset, content, language)).collect()
+}
+
+fn score_in(tokens: &[Token], var_name: &str, offset: usize, content: &str, language: &LanguagePattern) -> Option<Score> {
+    let rule = bad_variable_rule(var_name)?;
+
+    let genericness = match rule {
+        "generic name" => 30,
+        "doubled letter" => 25,
+        _ => 20,
+    };
+    let length = 4usize.saturating_sub(var_name.len()) as u32 * 10;
+
+    let declaration = tokens.iter().position(|token| token.offset >= offset).unwrap_or(tokens.len());
+    let scope = match language {
+        LanguagePattern::Python => indented_scope(tokens, content, declaration),
+        LanguagePattern::Ruby => 0..tokens.len(),
+        _ => braced_scope(tokens, declaration),
+    };
+    let lines: Vec<usize> = scope
+        .filter(|&i| tokens[i].kind == TokenKind::Ident && tokens[i].text == var_name)
+        // A member of something else: `self.tmp`, `obj?.x`
+        .filter(|&i| i == 0 || !(tokens[i - 1].is(".") || tokens[i - 1].is("?.")))
+        .map(|i| tokens[i].line)
+        .collect();
+    let uses = lines.len().saturating_sub(1) as u32;
+    let span = match (lines.first(), lines.last()) {
+        (Some(first), Some(last)) => (last - first) as u32,
+        _ => 0,
+    };
+    let distance = lines.windows(2).map(|pair| (pair[1] - pair[0]) as u32).max().unwrap_or(0);
+
+    let factors = vec![
+        (rule, genericness),
+        ("short name", length),
+        ("uses", (uses * 2).min(20)),
+        ("scope span", (span / 10).min(30)),
+        ("distance between uses", (distance / 5).min(20)),
+    ];
+    let total = factors.iter().map(|(_, points)| points).sum();
+    Some(Score { total, factors })
+}
+
+// The tokens a declaration at tokens[at] is visible in: the innermost `{ }` around it, or
+// for a parameter or `for` header, the block that follows its brackets. Without one,
+// the whole file.
+fn braced_scope(tokens: &[Token], at: usize) -> std::ops::Range<usize> {
+    let mut depth = 0usize;
+    for i in (0..at.min(tokens.len())).rev() {
+        if tokens[i].closes() {
+            depth += 1;
+        } else if tokens[i].opens() {
+            if depth > 0 {
+                depth -= 1;
+                continue;
+            }
+            if tokens[i].is("{") && !is_pattern(tokens, i) {
+                return i..block_end(tokens, i);
+            }
+            if tokens[i].is("{") {
+                continue;
+            }
+            // `fn f(x: u8) -> u8 {`, `for (int i = 0; ...) {`; a `(` with no block right
+            // after it (`(x) => x + 1`) lives in whatever is around it
+            let close = lexer::matching(tokens, i);
+            let body = tokens.iter().skip(close + 1).position(|token| token.opens() || token.closes() || token.is(";")).map(|j| close + 1 + j);
+            if let Some(body) = body.filter(|&body| tokens[body].is("{")) {
+                return i..block_end(tokens, body);
+            }
+        }
+    }
+    0..tokens.len()
+}
+
+// Index just past the bracket closing the one at open
+fn block_end(tokens: &[Token], open: usize) -> usize {
+    (lexer::matching(tokens, open) + 1).min(tokens.len())
+}
+
+// Whether the `{` at open destructures rather than starts a block: `const { a } = o`,
+// `function f({ a, b })`, `let Point { x, y } = p`
+fn is_pattern(tokens: &[Token], open: usize) -> bool {
+    let before = open.checked_sub(1).map(|j| &tokens[j]);
+    let after = tokens.get(lexer::matching(tokens, open) + 1);
+    before.is_some_and(|token| ["let", "const", "var", "(", ",", "[", "|"].iter().any(|word| token.is(word)))
+        || after.is_some_and(|token| token.is("=") || token.is("=>"))
+}
+
+// The tokens a Python declaration at tokens[at] is visible in: the body of the `def` it's
+// in, including the `def` line for its parameters; the whole module outside any.
+fn indented_scope(tokens: &[Token], content: &str, at: usize) -> std::ops::Range<usize> {
+    let lines: Vec<&str> = content.lines().collect();
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let is_def = |line: &str| line.trim_start().starts_with("def ") || line.trim_start().starts_with("async def ");
+    let Some(line) = tokens.get(at).map(|token| token.line - 1) else {
+        return 0..tokens.len();
+    };
+
+    // Back to the `def` whose body (or header) holds the line
+    let mut owner = None;
+    let mut current = indent(lines[line]) + usize::from(is_def(lines[line]));
+    for i in (0..=line).rev() {
+        let text = lines[i];
+        if text.trim().is_empty() || indent(text) >= current {
+            continue;
+        }
+        if is_def(text) {
+            owner = Some(i);
+            break;
+        }
+        current = indent(text);
+    }
+    let Some(owner) = owner else {
+        return 0..tokens.len();
+    };
+    // The body starts after the header, which may run over several lines or not be
+    // finished yet
+    let header_end = match tokens.iter().position(|token| token.line > owner && token.is("(")) {
+        Some(open) => tokens.get(lexer::matching(tokens, open)).map_or(lines.len(), |close| close.line - 1),
+        None => owner,
+    };
+    let end = (header_end + 1..lines.len())
+        .find(|&i| !lines[i].trim().is_empty() && indent(lines[i]) <= indent(lines[owner]))
+        .unwrap_or(lines.len());
+    let first = tokens.iter().position(|token| token.line > owner).unwrap_or(tokens.len());
+    let last = tokens.iter().position(|token| token.line > end).unwrap_or(tokens.len());
+    first..last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(var_name: &str, content: &str, language: &LanguagePattern) -> u32 {
+        score_variable(var_name, content, language).unwrap().total
+    }
+
+    #[test]
+    fn a_namesake_in_another_function_does_not_count() {
+        let alone = "def f():\n    tmp = 1\n    return tmp\n";
+        let with_namesake = format!("{}\n\ndef g():\n    tmp = 2\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n    print(tmp)\n", alone);
+        assert_eq!(total("tmp", &with_namesake, &LanguagePattern::Python), total("tmp", alone, &LanguagePattern::Python));
+
+        let alone = "fn f() -> u8 {\n    let tmp = 1;\n    tmp\n}\n";
+        let with_namesake = format!("{}\nfn g(tmp: u8) -> u8 {{\n{}    tmp\n}}\n", alone, "    // ...\n".repeat(40));
+        assert_eq!(total("tmp", &with_namesake, &LanguagePattern::Rust), total("tmp", alone, &LanguagePattern::Rust));
+    }
+
+    #[test]
+    fn strings_comments_and_members_do_not_count() {
+        let plain = "function f() {\n  const tmp = 1;\n  return tmp;\n}\n";
+        let noisy = "function f() {\n  const tmp = 1; // tmp tmp\n  return tmp + \"tmp\".length + this.tmp;\n}\n";
+        assert_eq!(total("tmp", noisy, &LanguagePattern::JavaScript), total("tmp", plain, &LanguagePattern::JavaScript));
+    }
+
+    #[test]
+    fn parameters_are_scored_over_their_function_body() {
+        let content = "def f(\n    tmp,\n):\n    a = tmp\n\n\n\n\n\n\n\n\n\n    return tmp\n\ntmp2 = 1\n";
+        let score = score_variable("tmp", content, &LanguagePattern::Python).unwrap();
+        assert!(score.factors.contains(&("uses", 4)), "{:?}", score.factors);
+        assert!(score.factors.contains(&("scope span", 1)), "{:?}", score.factors);
+    }
+
+    #[test]
+    fn unfinished_code_is_scored_without_panicking() {
+        let files = [
+            (LanguagePattern::Python, "def f(\n    tmp,\n):\n    return tmp\n"),
+            (LanguagePattern::Rust, "fn f(tmp: u8) -> u8 {\n    let Point { x, .. } = p;\n    tmp + x\n}\n"),
+            (LanguagePattern::JavaScript, "const { tmp } = o;\nfor (let x of xs) { f(tmp, x); }\n"),
+        ];
+        for (language, content) in &files {
+            for end in 0..=content.len() {
+                let prefix = &content[..end];
+                for offset in [0, end / 2, end] {
+                    score_declaration("tmp", offset, prefix, language);
+                    score_declaration("x", offset, prefix, language);
+                }
+            }
+        }
+    }
+}
+
@@ -0,0 +1,510 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::language_patterns::{BindingKind, LanguagePattern};
+use crate::lexer::{self, Token, TokenKind};
+
+const RUST: &LanguagePattern = &LanguagePattern::Rust;
+
+// Macros whose first string argument is a format string, where `{name}` reads a variable
+const FORMAT_MACROS: &[&str] = &[
+    "format", "format_args", "print", "println", "eprint", "eprintln", "write", "writeln", "panic", "assert", "assert_eq",
+    "assert_ne", "debug_assert", "debug_assert_eq", "debug_assert_ne", "unreachable", "todo", "unimplemented", "trace",
+    "debug", "info", "warn", "error",
+];
+
+// What follows these is the name of an item, never a variable
+const ITEM_KEYWORDS: &[&str] = &["fn", "struct", "enum", "mod", "trait", "type", "union"];
+
+// Whatever these start ends at a block, never at struct fields, even after a type
+// name: `if x == Foo::Bar {`, `fn new() -> Self {`, `impl Point {`
+const BLOCK_HEADERS: &[&str] = &["if", "while", "match", "fn", "impl", "trait", "mod", "enum"];
+
+// Tokens a closure's opening `|` can follow; anywhere else `|` is an or
+const BEFORE_CLOSURE: &[&str] = &["(", "[", "{", ",", ";", "=", "=>", ":", "move", "return", "async"];
+
+// Every name bound in content: `let`, `if let` and `while let` patterns including
+// tuples, structs and slices, `for` patterns, match arm patterns, every parameter
+// of a `fn` or closure. Attributes and macro_rules! bodies are skipped.
+pub(crate) fn bindings(content: &str) -> Vec<(usize, BindingKind, &str)> {
+    let tokens = code_tokens(content);
+    let mut found = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        if token.is("let") {
+            let conditional = previous.is_some_and(|previous| previous.is("if") || previous.is("while") || previous.is("&&"));
+            let kind = if conditional { BindingKind::PatternBinding } else { BindingKind::Variable };
+            let end = lexer::top_level(&tokens[i + 1..], |token| token.is("=") || token.is(":") || token.is(";")).map_or(tokens.len(), |end| i + 1 + end);
+            pattern_bindings(&tokens[i + 1..end], kind, &mut found);
+        } else if token.is("for") && is_loop(previous) {
+            if let Some(end) = lexer::top_level(&tokens[i + 1..], |token| token.is("in")) {
+                pattern_bindings(&tokens[i + 1..i + 1 + end], BindingKind::LoopIndex, &mut found);
+            }
+        } else if token.is("fn") && tokens.get(i + 1).is_some_and(|name| name.kind == TokenKind::Ident) {
+            let mut open = i + 2;
+            if tokens.get(open).is_some_and(|token| token.is("<")) {
+                open = angle_end(&tokens, open) + 1;
+            }
+            if tokens.get(open).is_some_and(|token| token.is("(")) {
+                let close = lexer::matching(&tokens, open);
+                parameters(&tokens[open + 1..close], BindingKind::Parameter, &mut found);
+            }
+        } else if token.is("|") && previous.is_none_or(|previous| BEFORE_CLOSURE.iter().any(|before| previous.is(before))) {
+            if let Some(end) = lexer::top_level(&tokens[i + 1..], |token| token.is("|")) {
+                parameters(&tokens[i + 1..i + 1 + end], BindingKind::LambdaParam, &mut found);
+            }
+        } else if token.is("=>") {
+            let pattern = &tokens[arm_start(&tokens, i)..i];
+            let guard = lexer::top_level(pattern, |token| token.is("if")).unwrap_or(pattern.len());
+            pattern_bindings(&pattern[..guard], BindingKind::PatternBinding, &mut found);
+        }
+    }
+    found
+}
+
+// The names a pattern binds: `x`, `mut x`, `(a, b)`, `Point { x, y: py, .. }`,
+// `Some(Ok(v))`, `[first, rest @ ..]`. Paths, variants, constants and field names aren't bindings.
+fn pattern_bindings<'a>(tokens: &[Token<'a>], kind: BindingKind, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Ident || lexer::is_keyword(RUST, token.text) || token.text == "_" {
+            continue;
+        }
+        // CamelCase is a type or variant, SCREAMING_CASE a constant
+        if !token.text.starts_with(|c: char| c.is_lowercase() || c == '_') {
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        let next = tokens.get(i + 1);
+        if previous.is_some_and(|previous| previous.is("::") || previous.is(".")) {
+            continue;
+        }
+        if next.is_some_and(|next| ["(", "{", "::", "!", ":", "."].iter().any(|after| next.is(after))) {
+            continue;
+        }
+        found.push((token.offset, kind, token.text));
+    }
+}
+
+// A fn or closure parameter list: each parameter's pattern, up to its type
+fn parameters<'a>(tokens: &[Token<'a>], kind: BindingKind, found: &mut Vec<(usize, BindingKind, &'a str)>) {
+    let mut depth = 0usize;
+    // Inside a parameter's type, where `<` and `>` nest too
+    let mut angles: Option<usize> = None;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.opens() {
+            depth += 1;
+        } else if token.closes() {
+            depth = depth.saturating_sub(1);
+        } else if depth > 0 {
+            continue;
+        } else if let Some(angle) = angles.as_mut() {
+            if token.is("<") {
+                *angle += 1;
+            } else if token.is(">") {
+                *angle = angle.saturating_sub(1);
+            } else if token.is(",") && *angle == 0 {
+                angles = None;
+                start = i + 1;
+            }
+        } else if token.is(":") {
+            pattern_bindings(&tokens[start..i], kind, found);
+            angles = Some(0);
+        } else if token.is(",") {
+            pattern_bindings(&tokens[start..i], kind, found);
+            start = i + 1;
+        }
+    }
+    if angles.is_none() {
+        pattern_bindings(&tokens[start..], kind, found);
+    }
+}
+
+// Where the match arm whose `=>` is at arrow starts: after the previous arm's comma
+// or block, or after the match's opening brace
+fn arm_start(tokens: &[Token], arrow: usize) -> usize {
+    let mut j = arrow;
+    while j > 0 {
+        let token = &tokens[j - 1];
+        if token.closes() {
+            let open = lexer::opening(tokens, j - 1);
+            if token.is("}") && open > 0 && tokens[open - 1].is("=>") {
+                return j;
+            }
+            j = open;
+        } else if token.opens() || token.is(",") || token.is("=>") {
+            return j;
+        } else {
+            j -= 1;
+        }
+    }
+    0
+}
+
+// Index of the `>` closing the generics opened at open
+fn angle_end(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is("<") {
+            depth += 1;
+        } else if token.is(">") {
+            depth -= 1;
+            if depth == 0 {
+                return i;
+            }
+        }
+    }
+    tokens.len() - 1
+}
+
+// content's tokens without comments, line breaks, attributes and macro_rules! bodies
+fn code_tokens(content: &str) -> Vec<Token<'_>> {
+    let tokens: Vec<Token> = lexer::tokenize(content, RUST)
+        .into_iter()
+        .filter(|token| !matches!(token.kind, TokenKind::Comment | TokenKind::Newline))
+        .collect();
+    let mut code = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        let attribute = token.is("#") && tokens.get(i + 1).is_some_and(|next| next.is("[") || next.is("!"));
+        let macro_rules = token.is("macro_rules") && tokens.get(i + 1).is_some_and(|next| next.is("!"));
+        if attribute || macro_rules {
+            match tokens[i..].iter().position(|token| token.opens()) {
+                Some(open) => i = lexer::matching(&tokens, i + open) + 1,
+                None => i = tokens.len(),
+            }
+            continue;
+        }
+        code.push(token);
+        i += 1;
+    }
+    code
+}
+
+// What an open bracket is
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    // The fields of a struct expression or pattern: `Point { x, y: 0 }`
+    Struct,
+    // The fields of a struct or union definition: `struct G<T> where T: Copy { v: T }`
+    Definition,
+    Other,
+}
+
+// A format macro's arguments
+struct FormatCall<'a> {
+    // Index of the closing bracket
+    close: usize,
+    // How many brackets are open inside the call's own
+    depth: usize,
+    // `name = value` arguments, which `{name}` reads instead of a variable
+    named: HashSet<&'a str>,
+    seen_format_string: bool,
+}
+
+// Renames every use of each old name in code and in format strings' inline arguments
+// (`format!("{x:>w$}")`), expanding field shorthand (`Point { x }` becomes
+// `Point { x: renamed }`) and leaving alone fields, methods, paths, macros, items,
+// lifetimes, comments and strings
+pub(crate) fn rename(content: &str, replacements: &[(String, String)]) -> String {
+    let names: HashMap<&str, &str> = replacements.iter().map(|(old, new)| (old.as_str(), new.as_str())).collect();
+    let tokens = code_tokens(content);
+    let mut edits = Vec::new();
+    let mut brackets: Vec<Bracket> = Vec::new();
+    // Block headers being read: the depth each started at, and whether it's in a pattern
+    let mut headers: Vec<(usize, bool)> = Vec::new();
+    let mut formats: Vec<FormatCall> = Vec::new();
+    // Indexes of the names of format macros' named arguments
+    let mut named_arguments: HashSet<usize> = HashSet::new();
+    // The depth a `struct` or `union` item started at, until its fields open or its `;`
+    let mut item: Option<usize> = None;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|j| &tokens[j]);
+        let next = tokens.get(i + 1);
+        let header = headers.last_mut().filter(|(depth, _)| *depth == brackets.len());
+        match token.kind {
+            TokenKind::Ident if token.is("for") && is_loop(previous) => headers.push((brackets.len(), true)),
+            TokenKind::Ident if BLOCK_HEADERS.contains(&token.text) => headers.push((brackets.len(), false)),
+            TokenKind::Ident if token.is("struct") || (token.is("union") && next.is_some_and(|next| next.kind == TokenKind::Ident)) => {
+                item = Some(brackets.len());
+            }
+            TokenKind::Ident if token.is("let") => {
+                if let Some((_, pattern)) = header {
+                    *pattern = true;
+                }
+            }
+            TokenKind::Ident if token.is("in") => {
+                if let Some((_, pattern)) = header {
+                    *pattern = false;
+                }
+            }
+            TokenKind::Punct if token.is("=") => {
+                if let Some((_, pattern)) = header {
+                    *pattern = false;
+                }
+            }
+            TokenKind::Punct if token.is(";") || token.is(",") || token.is("=>") => {
+                // A match guard or a `fn` type ends without a block of its own
+                headers.retain(|(depth, _)| *depth < brackets.len());
+                if token.is(";") && item == Some(brackets.len()) {
+                    // A tuple or unit struct
+                    item = None;
+                }
+            }
+            TokenKind::Punct if token.opens() => {
+                let header_block = token.is("{") && header.is_some_and(|(_, pattern)| !*pattern);
+                if header_block {
+                    headers.pop();
+                }
+                let definition = token.is("{") && item == Some(brackets.len());
+                let fields = token.is("{") && !header_block && previous.is_some_and(is_type_name);
+                let kind = if definition {
+                    Bracket::Definition
+                } else if fields {
+                    Bracket::Struct
+                } else {
+                    Bracket::Other
+                };
+                brackets.push(kind);
+                if definition {
+                    item = None;
+                }
+
+                let macro_name = previous.filter(|_| i >= 2 && tokens[i - 2].kind == TokenKind::Ident && tokens[i - 1].is("!")).map(|_| tokens[i - 2].text);
+                if macro_name.is_some_and(|name| FORMAT_MACROS.contains(&name)) {
+                    let close = lexer::matching(&tokens, i);
+                    let named = named_argument_indexes(&tokens, i, close);
+                    let call = FormatCall { close, depth: brackets.len(), named: named.iter().map(|&j| tokens[j].text).collect(), seen_format_string: false };
+                    named_arguments.extend(named);
+                    formats.push(call);
+                }
+            }
+            TokenKind::Punct if token.closes() => {
+                brackets.pop();
+                headers.retain(|(depth, _)| *depth <= brackets.len());
+                if formats.last().is_some_and(|call| call.close == i) {
+                    formats.pop();
+                }
+            }
+            TokenKind::Str => {
+                let Some(call) = formats.last_mut().filter(|call| call.depth == brackets.len() && !call.seen_format_string) else {
+                    continue;
+                };
+                call.seen_format_string = true;
+                for (start, end) in inline_arguments(token.text) {
+                    let name = &token.text[start..end];
+                    if let Some(new_name) = names.get(name).filter(|_| !call.named.contains(name)) {
+                        edits.push((token.offset + start, end - start, new_name.to_string()));
+                    }
+                }
+            }
+            TokenKind::Ident => {
+                let Some(new_name) = names.get(token.text) else {
+                    continue;
+                };
+                let member = previous.is_some_and(|previous| previous.is(".") || previous.is("::") || previous.is("$"));
+                let item_name = previous.is_some_and(|previous| ITEM_KEYWORDS.iter().any(|keyword| previous.is(keyword)));
+                let path_or_macro = next.is_some_and(|next| next.is("::") || next.is("!"));
+                let definition = brackets.last() == Some(&Bracket::Definition);
+                if member || item_name || path_or_macro || definition || named_arguments.contains(&i) {
+                    continue;
+                }
+                if brackets.last() == Some(&Bracket::Struct) {
+                    // Skip back over `ref` and `mut` to where the field starts
+                    let mut start = i;
+                    while start > 0 && (tokens[start - 1].is("ref") || tokens[start - 1].is("mut")) {
+                        start -= 1;
+                    }
+                    let field_start = start > 0 && (tokens[start - 1].is("{") || tokens[start - 1].is(","));
+                    if field_start && next.is_some_and(|next| next.is(":")) {
+                        // A field name
+                        continue;
+                    }
+                    if field_start && next.is_some_and(|next| next.is(",") || next.is("}")) {
+                        // Shorthand: the field keeps its name and gets the variable by its new one
+                        edits.push((tokens[start].offset, 0, format!("{}: ", token.text)));
+                    }
+                }
+                edits.push((token.offset, token.text.len(), new_name.to_string()));
+            }
+            _ => {}
+        }
+    }
+    lexer::apply_edits(content, edits)
+}
+
+// `for` starts a loop unless it follows a type, as in `impl Trait for Type`
+fn is_loop(previous: Option<&Token>) -> bool {
+    previous.is_none_or(|previous| previous.kind == TokenKind::Punct && !matches!(previous.text, ">" | ")" | "]"))
+        || previous.is_some_and(|previous| matches!(previous.text, "else" | "return" | "in" | "move"))
+}
+
+// `Point`, `Self`, `DFA`, `a::B`: a name whose `{` opens struct fields rather than a
+// block. Constants before a block only come in block headers, which are told apart first.
+fn is_type_name(token: &Token) -> bool {
+    token.kind == TokenKind::Ident && token.text.starts_with(|c: char| c.is_uppercase())
+}
+
+// Indexes of the `name` in each `name = value` argument between open and close
+fn named_argument_indexes(tokens: &[Token], open: usize, close: usize) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut depth = 0usize;
+    for i in open + 1..close {
+        if tokens[i].opens() {
+            depth += 1;
+        } else if tokens[i].closes() {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && tokens[i].kind == TokenKind::Ident && (tokens[i - 1].is(",") || i == open + 1) && tokens.get(i + 1).is_some_and(|next| next.is("=")) {
+            found.push(i);
+        }
+    }
+    found
+}
+
+// Byte ranges of the variables a format string reads inline: the `x` in `{x}` and
+// `{x:?}`, and the `w` and `p` in `{:w$.p$}`
+fn inline_arguments(literal: &str) -> Vec<(usize, usize)> {
+    let bytes = literal.as_bytes();
+    let identifier_end = |start: usize| {
+        let starts = bytes.get(start).is_some_and(|&c| c.is_ascii_alphabetic() || c == b'_');
+        let len = if starts { bytes[start..].iter().take_while(|&&c| c.is_ascii_alphanumeric() || c == b'_').count() } else { 0 };
+        start + len
+    };
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'{' {
+            i += 1;
+            continue;
+        }
+        if bytes.get(i + 1) == Some(&b'{') {
+            i += 2;
+            continue;
+        }
+        let start = i + 1;
+        let end = identifier_end(start);
+        if end > start && matches!(bytes.get(end), Some(b'}' | b':')) {
+            found.push((start, end));
+        }
+        let close = literal[start..].find('}').map_or(bytes.len(), |close| start + close);
+        // Widths and precisions given as `name$` in the spec
+        let mut j = end;
+        while j < close {
+            let word_end = identifier_end(j);
+            if word_end > j {
+                if bytes.get(word_end) == Some(&b'$') && !bytes[j - 1].is_ascii_alphanumeric() {
+                    found.push((j, word_end));
+                }
+                j = word_end;
+            } else {
+                j += 1;
+            }
+        }
+        i = close + 1;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamed(content: &str, old: &str, new: &str) -> String {
+        rename(content, &[(old.to_string(), new.to_string())])
+    }
+
+    #[test]
+    fn generic_struct_fields_keep_their_names() {
+        let content = "struct G<T> { v: T }\nfn f<T>(v: T) -> G<T> { G { v } }\nimpl<T> G<T> { fn get(&self) -> &T { &self.v } }\n";
+        assert_eq!(
+            renamed(content, "v", "w"),
+            "struct G<T> { v: T }\nfn f<T>(w: T) -> G<T> { G { v: w } }\nimpl<T> G<T> { fn get(&self) -> &T { &self.v } }\n"
+        );
+    }
+
+    #[test]
+    fn all_caps_struct_fields_keep_their_names() {
+        let content = "pub struct DFA { stride2: usize }\nfn f(stride2: usize) -> DFA { DFA { stride2 } }\n";
+        assert_eq!(renamed(content, "stride2", "s"), "pub struct DFA { stride2: usize }\nfn f(s: usize) -> DFA { DFA { stride2: s } }\n");
+    }
+
+    #[test]
+    fn where_clause_and_tuple_structs() {
+        let content = "struct P<'p>(&'p str);\nstruct W<T> where T: Copy { i: T }\nfn f(i: u8) -> W<u8> { W { i } }\n";
+        assert_eq!(renamed(content, "i", "n"), "struct P<'p>(&'p str);\nstruct W<T> where T: Copy { i: T }\nfn f(n: u8) -> W<u8> { W { i: n } }\n");
+    }
+
+    #[test]
+    fn constants_before_blocks_are_not_struct_literals() {
+        let content = "fn f(x: u8) { if x == MAX { g(x) } while x < LIMIT { x } }\n";
+        assert_eq!(renamed(content, "x", "y"), "fn f(y: u8) { if y == MAX { g(y) } while y < LIMIT { y } }\n");
+    }
+
+    fn bound(content: &str) -> Vec<(usize, BindingKind, &str)> {
+        let mut found = bindings(content);
+        found.sort_by_key(|(offset, _, _)| *offset);
+        found
+    }
+
+    #[test]
+    fn patterns_parameters_and_closures() {
+        let content = "fn f(mut a: u8, (b, c): (u8, u8)) {\n    let Point { x, y: py, .. } = p;\n    for (i, v) in it {}\n    if let Some(Ok(w)) = r {}\n    let g = |h| h;\n}\n";
+        let names: Vec<(BindingKind, &str)> = bound(content).into_iter().map(|(_, kind, name)| (kind, name)).collect();
+        assert_eq!(
+            names,
+            [
+                (BindingKind::Parameter, "a"),
+                (BindingKind::Parameter, "b"),
+                (BindingKind::Parameter, "c"),
+                (BindingKind::Variable, "x"),
+                (BindingKind::Variable, "py"),
+                (BindingKind::LoopIndex, "i"),
+                (BindingKind::LoopIndex, "v"),
+                (BindingKind::PatternBinding, "w"),
+                (BindingKind::Variable, "g"),
+                (BindingKind::LambdaParam, "h"),
+            ]
+        );
+        let (offset, _, _) = bound(content)[0];
+        assert_eq!(&content[offset..offset + 1], "a");
+    }
+
+    #[test]
+    fn field_shorthand_is_expanded() {
+        let content = "fn f(x: u8, y: u8) -> Point { Point { x, y } }\n";
+        assert_eq!(renamed(content, "x", "px"), "fn f(px: u8, y: u8) -> Point { Point { x: px, y } }\n");
+    }
+
+    #[test]
+    fn self_fields_methods_and_paths_are_left_alone() {
+        let content = "fn f(&self, len: usize) -> usize { self.len + len.len() + len::MAX + Self::len(len) }\n";
+        assert_eq!(renamed(content, "len", "n"), "fn f(&self, n: usize) -> usize { self.len + n.len() + len::MAX + Self::len(n) }\n");
+    }
+
+    #[test]
+    fn macros_and_format_strings() {
+        let content = "fn f(x: u8) { println!(\"{x} {x:>w$} {}\", x, w = x); x!(); vec![x]; }\n";
+        assert_eq!(renamed(content, "x", "y"), "fn f(y: u8) { println!(\"{y} {y:>w$} {}\", y, w = y); x!(); vec![y]; }\n");
+    }
+
+    #[test]
+    fn lifetimes_and_char_literals_are_not_names() {
+        let content = "fn f<'a>(a: &'a str) -> char { 'a: loop { break 'a; } let c = 'a'; a.chars().next().unwrap_or(c) }\n";
+        assert_eq!(
+            renamed(content, "a", "s"),
+            "fn f<'a>(s: &'a str) -> char { 'a: loop { break 'a; } let c = 'a'; s.chars().next().unwrap_or(c) }\n"
+        );
+    }
+
+    #[test]
+    fn every_prefix_of_a_file_being_typed_is_handled() {
+        let content = "#[derive(Debug)]\nstruct G<'a, T> where T: Copy { v: &'a T }\nmacro_rules! m { ($x:expr) => { $x } }\nimpl<'a, T: Copy> G<'a, T> {\n    fn f(&self, (a, b): (u8, u8), x: &[u8]) -> Option<u8> {\n        let Point { x: px, .. } = p;\n        for (i, v) in x.iter().enumerate() { if let Some(w) = v.checked_add(a) { return Some(w); } }\n        let c = |y: u8| y + b;\n        match px { 0 => None, n @ 1..=9 => Some(c(n)), _ => { println!(\"{px:>w$}\", w = 3); None } }\n    }\n}\n";
+        let replacements = [("x".to_string(), "bytes".to_string()), ("v".to_string(), "value".to_string())];
+        for end in (0..=content.len()).filter(|&end| content.is_char_boundary(end)) {
+            bindings(&content[..end]);
+            rename(&content[..end], &replacements);
+        }
+    }
+}
+
@@ -0,0 +1,107 @@
+use crate::language_patterns::LanguagePattern;
+use crate::lexer::{self, TokenKind};
+
+// The shape of a file as far as renaming identifiers is concerned. Renaming one
+// identifier to another must leave all of it exactly as it was.
+#[derive(Debug, Default, PartialEq)]
+struct Structure {
+    // Every bracket outside strings and comments, with its line
+    brackets: Vec<(char, usize)>,
+    // Strings, characters, comments and JSX markup, in order, with the line each starts on
+    literals: Vec<(Literal, usize)>,
+    identifiers: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Literal {
+    String,
+    Char,
+    Comment,
+    Markup,
+}
+
+// Err with the reason when rewritten isn't structurally the same as original: its
+// brackets, its strings and comments and its number of identifiers must all match.
+// Catches renames that turn a char literal into a lifetime, a name into a keyword,
+// or a replacement that swallowed a quote.
+pub fn check_rewrite(original: &str, rewritten: &str, language: &LanguagePattern) -> Result<(), String> {
+    let before = structure(original, language);
+    let after = structure(rewritten, language);
+
+    if let Some(i) = first_difference(&before.literals, &after.literals) {
+        let line = after.literals.get(i).or(before.literals.get(i)).map_or(0, |(_, line)| *line);
+        return Err(format!("strings or comments changed near line {}", line));
+    }
+    if let Some(i) = first_difference(&before.brackets, &after.brackets) {
+        let line = after.brackets.get(i).or(before.brackets.get(i)).map_or(0, |(_, line)| *line);
+        return Err(format!("brackets changed near line {}", line));
+    }
+    if before.identifiers != after.identifiers {
+        return Err(format!("identifier count changed from {} to {}", before.identifiers, after.identifiers));
+    }
+    Ok(())
+}
+
+// Only kinds are compared; a renamed identifier may move later tokens along a line but not across lines
+fn first_difference<T: PartialEq>(before: &[(T, usize)], after: &[(T, usize)]) -> Option<usize> {
+    let differs = before.iter().zip(after).position(|((a, line_a), (b, line_b))| a != b || line_a != line_b);
+    differs.or((before.len() != after.len()).then(|| before.len().min(after.len())))
+}
+
+fn structure(content: &str, language: &LanguagePattern) -> Structure {
+    let mut found = Structure::default();
+    let tokens = lexer::tokenize(content, language);
+    for (i, token) in tokens.iter().enumerate() {
+        // A name before a single colon labels what follows (a field, a type annotation), and
+        // one before `as` is what an import or export calls it, so expanding shorthand
+        // `{ x }` into `{ x: renamed }` or `{ renamed as x }` doesn't count as a new identifier
+        let label = tokens.get(i + 1).is_some_and(|next| next.is(":") || next.is("as"));
+        match token.kind {
+            TokenKind::Punct if matches!(token.text, "(" | ")" | "[" | "]" | "{" | "}") => {
+                found.brackets.push((token.text.chars().next().unwrap_or_default(), token.line));
+            }
+            TokenKind::Str => found.literals.push((Literal::String, token.line)),
+            TokenKind::Char => found.literals.push((Literal::Char, token.line)),
+            TokenKind::Comment => found.literals.push((Literal::Comment, token.line)),
+            TokenKind::Markup => found.literals.push((Literal::Markup, token.line)),
+            TokenKind::Ident | TokenKind::Lifetime if !label && !lexer::is_keyword(language, token.text) => found.identifiers += 1,
+            _ => {}
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_plain_rename_passes() {
+        let rust = "fn f<'a>(c: &'a str) -> char {\n    let d = 'x'; // c\n    d\n}\n";
+        let renamed = "fn f<'a>(yourmom: &'a str) -> char {\n    let yourMom = 'x'; // c\n    yourMom\n}\n";
+        assert_eq!(check_rewrite(rust, renamed, &LanguagePattern::Rust), Ok(()));
+        // Shorthand expanded to keep a field's name
+        let js = "const x = 1;\nf({ x });\n";
+        assert_eq!(check_rewrite(js, "const yourmom = 1;\nf({ x: yourmom });\n", &LanguagePattern::JavaScript), Ok(()));
+    }
+
+    #[test]
+    fn a_char_turned_lifetime_fails() {
+        let rust = "let c = 'b';\nlet d = c;\n";
+        let result = check_rewrite(rust, "let c = 'yourmom';\nlet d = c;\n", &LanguagePattern::Rust);
+        assert_eq!(result, Err("strings or comments changed near line 1".to_string()));
+    }
+
+    #[test]
+    fn a_name_turned_keyword_fails() {
+        let python = "tmp = 1\nprint(tmp)\n";
+        let result = check_rewrite(python, "class = 1\nprint(class)\n", &LanguagePattern::Python);
+        assert_eq!(result, Err("identifier count changed from 3 to 1".to_string()));
+    }
+
+    #[test]
+    fn a_swallowed_bracket_fails() {
+        let result = check_rewrite("f(x)\n", "f(yourmom\n", &LanguagePattern::Python);
+        assert_eq!(result, Err("brackets changed near line 1".to_string()));
+    }
+}
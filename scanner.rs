@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::baseline::{self, Baseline};
+use crate::cache::Cache;
+use crate::git::{self, ChangedLines};
+use crate::language_patterns::{BindingKind, LanguagePattern};
+use crate::rules::{self, bad_variable_rule, RuleProfile, Score};
+use crate::suppress::Suppressions;
+use crate::Warning;
+
+// One bad name, at its first declaration in the file
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub name: String,
+    // Byte offset of the declaration
+    pub offset: usize,
+    // 1-based
+    pub line: usize,
+    // The rule that flagged it, e.g. "generic name"
+    pub rule: &'static str,
+    pub score: Score,
+}
+
+// Every place a bad name is bound, sorted by position in the file
+pub(crate) fn find_bad_declarations<'a>(content: &'a str, language: &LanguagePattern, profile: RuleProfile) -> Vec<(usize, &'a str)> {
+    let mut found: Vec<(usize, BindingKind, &str)> = language.find_bindings(content);
+    found.retain(|(_, _, var_name)| bad_variable_rule(var_name).is_some());
+    
+    // Stable sort: when patterns overlap, the one listed first decides the binding kind
+    found.sort_by_key(|(offset, _, _)| *offset);
+    found.dedup_by_key(|(offset, _, _)| *offset);
+    found
+        .into_iter()
+        .filter(|(_, kind, var_name)| !profile.allows(*kind, var_name))
+        .map(|(offset, _, var_name)| (offset, var_name))
+        .collect()
+}
+
+// Only the first declaration of each name
+pub(crate) fn first_declarations<'a>(found: &[(usize, &'a str)]) -> Vec<(usize, &'a str)> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    found.iter().copied().filter(|(_, var_name)| seen.insert(var_name)).collect()
+}
+
+pub(crate) fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+// Per-file restrictions on which findings count
+#[derive(Default)]
+pub(crate) struct FileFilter<'a> {
+    // Only names declared in these (1-based, inclusive) lines
+    declared_in: Option<&'a [(usize, usize)]>,
+    // Findings recorded in a baseline, plus this file's key in it
+    baseline: Option<(&'a Baseline, String)>,
+    report_unused_suppressions: bool,
+    profile: RuleProfile,
+    // Minimum severity score for a finding to count
+    threshold: u32,
+    cache: Option<&'a Cache>,
+}
+
+impl FileFilter<'_> {
+    pub(crate) fn keeps_declaration(&self, content: &str, offset: usize) -> bool {
+        let Some(ranges) = self.declared_in else {
+            return true;
+        };
+        let line = line_of(content, offset);
+        ranges.iter().any(|(start, end)| (*start..=*end).contains(&line))
+    }
+
+    pub(crate) fn keeps_finding(&self, content: &str, offset: usize, var_name: &str) -> bool {
+        match &self.baseline {
+            Some((baseline, file_key)) => {
+                !baseline.contains(file_key, var_name, &baseline::fingerprint(content, offset, var_name))
+            }
+            None => true,
+        }
+    }
+}
+
+// Finds bad names. The settings apply to every file scanned; changed lines and
+// baseline entries are looked up by each file's path. `Scanner::default()` reports
+// every bad name the default profile flags.
+#[derive(Default)]
+pub struct Scanner {
+    changed: Option<ChangedLines>,
+    baseline: Option<Baseline>,
+    report_unused_suppressions: bool,
+    profile: RuleProfile,
+    threshold: u32,
+    cache: Option<Cache>,
+}
+
+impl Scanner {
+    // Only names declared on these lines of each file (see git::changed_lines)
+    pub fn with_changed_lines(self, changed: ChangedLines) -> Self {
+        Scanner { changed: Some(changed), ..self }
+    }
+
+    // Skip findings recorded in this baseline
+    pub fn with_baseline(self, baseline: Baseline) -> Self {
+        Scanner { baseline: Some(baseline), ..self }
+    }
+
+    // Warn about `badvars:` comments that suppress nothing
+    pub fn with_unused_suppressions(self, report: bool) -> Self {
+        Scanner { report_unused_suppressions: report, ..self }
+    }
+
+    pub fn with_profile(self, profile: RuleProfile) -> Self {
+        Scanner { profile, ..self }
+    }
+
+    // Minimum severity score for a finding to count
+    pub fn with_threshold(self, threshold: u32) -> Self {
+        Scanner { threshold, ..self }
+    }
+
+    // Reuse the findings of files scanned before with the same content and settings
+    pub fn with_cache(self, cache: Cache) -> Self {
+        Scanner { cache: Some(cache), ..self }
+    }
+
+    pub fn changed_lines(&self) -> Option<&ChangedLines> {
+        self.changed.as_ref()
+    }
+
+    // Every bad name in content, in order of first declaration
+    pub fn scan(&self, file_path: &Path, content: &str, language: &LanguagePattern) -> (Vec<Finding>, Vec<Warning>) {
+        let filter = self.for_file(file_path);
+        let (found, warnings) = filtered_declarations(content, language, &filter);
+        let first: Vec<(usize, &str)> = first_declarations(&found)
+            .into_iter()
+            .filter(|(offset, var_name)| filter.keeps_finding(content, *offset, var_name))
+            .collect();
+        let scores = rules::score_declarations(&first, content, language);
+        let findings = first
+            .into_iter()
+            .zip(scores)
+            .filter_map(|((offset, var_name), score)| {
+                Some(Finding {
+                    name: var_name.to_string(),
+                    offset,
+                    line: line_of(content, offset),
+                    rule: bad_variable_rule(var_name)?,
+                    score: score?,
+                })
+            })
+            .collect();
+        (findings, warnings)
+    }
+
+    pub(crate) fn for_file(&self, file_path: &Path) -> FileFilter<'_> {
+        FileFilter {
+            // A file missing from the diff has no lines where a finding may be declared
+            declared_in: self
+                .changed
+                .as_ref()
+                .map(|changed| changed.get(&git::canonical(file_path)).map_or(&[][..], Vec::as_slice)),
+            baseline: self.baseline.as_ref().map(|baseline| (baseline, baseline::file_key(file_path))),
+            report_unused_suppressions: self.report_unused_suppressions,
+            profile: self.profile,
+            threshold: self.threshold,
+            cache: self.cache.as_ref(),
+        }
+    }
+}
+
+// Declarations that survive the file's filters and its `badvars:` suppression comments
+pub(crate) fn filtered_declarations<'a>(content: &'a str, language: &LanguagePattern, filter: &FileFilter) -> (Vec<(usize, &'a str)>, Vec<Warning>) {
+    // The unused suppression report needs the full pass
+    let cache = filter.cache.filter(|_| !filter.report_unused_suppressions);
+    let key = cache.map(|_| Cache::key(content, language, filter.profile, filter.threshold));
+    let cached = match (cache, &key) {
+        (Some(cache), Some(key)) => cache.get(key).and_then(|entries| {
+            // Borrow the names from content again; anything that doesn't line up is a miss
+            entries
+                .iter()
+                .map(|(offset, name)| content.get(*offset..offset + name.len()).filter(|found| found == name).map(|found| (*offset, found)))
+                .collect::<Option<Vec<_>>>()
+        }),
+        _ => None,
+    };
+    let (mut found, warnings) = match cached {
+        Some(found) => (found, Vec::new()),
+        None => {
+            let (found, warnings) = content_declarations(content, language, filter);
+            if let (Some(cache), Some(key)) = (cache, &key) {
+                // A cache that can't be written just means scanning again next time
+                let _ = cache.put(key, &found);
+            }
+            (found, warnings)
+        }
+    };
+    
+    // Depends on the file's path rather than its content, so it's never cached
+    found.retain(|(offset, _)| filter.keeps_declaration(content, *offset));
+    (found, warnings)
+}
+
+// The part of filtered_declarations that depends only on content and settings
+fn content_declarations<'a>(content: &'a str, language: &LanguagePattern, filter: &FileFilter) -> (Vec<(usize, &'a str)>, Vec<Warning>) {
+    let mut found = find_bad_declarations(content, language, filter.profile);
+    
+    if filter.threshold > 0 {
+        // A name's first declaration decides for all of them, as it's renamed everywhere
+        let first = first_declarations(&found);
+        let totals: HashMap<&str, u32> = first
+            .iter()
+            .zip(rules::score_declarations(&first, content, language))
+            .map(|((_, var_name), score)| (*var_name, score.map_or(0, |score| score.total)))
+            .collect();
+        found.retain(|(_, var_name)| totals[var_name] >= filter.threshold);
+    }
+    
+    let mut suppressions = Suppressions::parse(content, language);
+    found.retain(|(offset, var_name)| !suppressions.suppresses(line_of(content, *offset), var_name));
+    let mut warnings = Vec::new();
+    if filter.report_unused_suppressions {
+        for (line, comment) in suppressions.unused() {
+            warnings.push(Warning::UnusedSuppression { line, comment: comment.to_string() });
+        }
+    }
+    (found, warnings)
+}
@@ -0,0 +1,334 @@
+use regex::Regex;
+
+use crate::language_patterns::LanguagePattern;
+use crate::lexer;
+use crate::naming::Casing;
+use crate::rules::bad_variable_rule;
+
+// Types that say nothing about what a variable is for
+const PRIMITIVE_TYPES: &[&str] = &[
+    "int", "long", "short", "byte", "char", "float", "double", "bool", "boolean", "void", "auto",
+    "var", "val", "let", "const", "final", "string", "str", "usize", "isize", "u8", "u16", "u32",
+    "u64", "u128", "i8", "i16", "i32", "i64", "i128", "f32", "f64", "number", "any", "object",
+    "dynamic", "unsigned", "signed", "static", "mut", "self",
+];
+
+// Containers whose element type names the variable better than the container itself
+const CONTAINER_TYPES: &[&str] = &[
+    "vec", "list", "arraylist", "linkedlist", "set", "hashset", "btreeset", "array", "slice",
+    "iterable", "iterator", "collection", "seq", "sequence", "vecdeque", "deque", "queue", "stack",
+];
+
+// Verbs stripped from function names: read_config() -> config
+const CALL_VERBS: &[&str] = &["get", "read", "load", "fetch", "make", "create", "build", "parse", "find", "new", "open", "compute", "calc", "calculate"];
+
+// Calls whose result has a conventional name
+const KNOWN_CALLS: &[(&str, &str)] = &[
+    ("open", "file"),
+    ("fopen", "file"),
+    ("input", "line"),
+    ("readline", "line"),
+    ("len", "length"),
+    ("length", "length"),
+    ("count", "count"),
+    ("size", "size"),
+    ("sum", "total"),
+    ("keys", "keys"),
+    ("values", "values"),
+    ("split", "parts"),
+    ("lines", "lines"),
+    ("connect", "connection"),
+    ("now", "now"),
+];
+
+// Derives a name for the variable declared at `offset` from what surrounds it:
+// its type, what it is assigned, what it iterates over, or what a function
+// it is passed to calls the parameter. None when nothing useful is found.
+pub fn suggest_name(content: &str, offset: usize, var_name: &str, language: &LanguagePattern) -> Option<String> {
+    let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = content[offset..].find('\n').map_or(content.len(), |i| offset + i);
+    let before = &content[line_start..offset];
+    let after = &content[offset + var_name.len()..line_end];
+
+    // A heuristic that only comes up with an unusable name leaves the word to the next one
+    let heuristics: [&dyn Fn() -> Option<Vec<String>>; 4] = [
+        &|| from_loop(before, after),
+        &|| from_type(before, after),
+        &|| from_assignment(after),
+        &|| from_call_sites(content, var_name, language),
+    ];
+    let valid = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+    heuristics
+        .iter()
+        .filter_map(|heuristic| heuristic())
+        .map(|words| Casing::for_language(language).apply(&words))
+        .find(|name| {
+            name != var_name
+                && valid.is_match(name)
+                && bad_variable_rule(name).is_none()
+                && !is_primitive(name)
+                && !lexer::is_keyword(language, name)
+        })
+}
+
+// for x in items / for (const x of items) / for (T x : items) / foreach ($items as $x) / for _, x := range items
+fn from_loop(before: &str, after: &str) -> Option<Vec<String>> {
+    let forward = Regex::new(r"^\s*(?:in|of|:|<-)\s+([^{:)\n]+)").unwrap();
+    let go_range = Regex::new(r"^[^=]*:=\s*range\s+([^{\n]+)").unwrap();
+    let php = Regex::new(r"\bforeach\s*\(\s*\$?([^\s]+)\s+as\s+(?:\$\w+\s*=>\s*)?\$?$").unwrap();
+
+    let collection = if let Some(cap) = php.captures(before) {
+        cap[1].to_string()
+    } else if before.contains("for") {
+        let cap = forward.captures(after).or_else(|| go_range.captures(after))?;
+        cap[1].to_string()
+    } else {
+        return None;
+    };
+
+    let collection = collection.trim();
+    if collection.starts_with("range(") || collection.contains("..") {
+        return Some(vec!["index".to_string()]);
+    }
+    let mut words = split_words(&last_identifier(collection)?);
+    let last = words.pop()?;
+    let single = singular(&last)?;
+    words.push(single);
+    Some(words)
+}
+
+// let x: Vec<User> / x: User (Python, TS, Kotlin, Swift, Scala) / List<User> x / User x (C-family)
+fn from_type(before: &str, after: &str) -> Option<Vec<String>> {
+    let annotated = Regex::new(r"^\s*:\s*([A-Za-z_][\w:.<>, \[\]&*]*)").unwrap();
+    let prefixed = Regex::new(r"([A-Za-z_][\w.]*(?:<[^<>]*(?:<[^<>]*>[^<>]*)*>)?(?:\[\])*)[\s*&]+$").unwrap();
+
+    let type_text = match annotated.captures(after) {
+        Some(cap) => cap[1].trim().to_string(),
+        None => prefixed.captures(before)?[1].to_string(),
+    };
+    words_for_type(&type_text)
+}
+
+fn words_for_type(type_text: &str) -> Option<Vec<String>> {
+    let type_text = type_text.trim().trim_start_matches(['&', '*']).trim_start_matches("mut ").trim();
+    if let Some(inner) = type_text.strip_suffix("[]") {
+        return pluralise(words_for_type(inner)?);
+    }
+    if let Some(inner) = type_text.strip_prefix("[]") {
+        return pluralise(words_for_type(inner)?);
+    }
+    if type_text.starts_with('[') && type_text.ends_with(']') {
+        let inner = type_text[1..type_text.len() - 1].split(';').next()?;
+        return pluralise(words_for_type(inner)?);
+    }
+
+    let (outer, inner) = match type_text.split_once('<') {
+        Some((outer, rest)) => (outer.trim(), Some(rest.trim_end_matches('>').trim())),
+        None => (type_text, None),
+    };
+    let outer = outer.rsplit([':', '.']).next().unwrap_or(outer);
+
+    if CONTAINER_TYPES.contains(&outer.to_lowercase().as_str()) {
+        return pluralise(words_for_type(inner?.split(',').next()?)?);
+    }
+    match outer.to_lowercase().as_str() {
+        "option" | "optional" | "box" | "rc" | "arc" | "refcell" | "cell" => return words_for_type(inner?),
+        "hashmap" | "btreemap" | "map" | "dictionary" | "dict" => {
+            let value = inner?.split(',').nth(1)?;
+            let mut words = words_for_type(value)?;
+            words.push("by".to_string());
+            words.push("key".to_string());
+            return Some(words);
+        }
+        _ => {}
+    }
+    if is_primitive(outer) {
+        return None;
+    }
+    Some(split_words(outer))
+}
+
+// x = open(...) / x = Foo::new() / x = new Foo() / x = get_user(...) / x = items.len()
+fn from_assignment(after: &str) -> Option<Vec<String>> {
+    let rhs = Regex::new(r"^\s*(?::=|=)\s*(?:await\s+|new\s+)?([^;\n]+)").unwrap();
+    let rhs = rhs.captures(after)?[1].trim().to_string();
+    if rhs.starts_with('=') {
+        return None;
+    }
+
+    // The last call in a chain names the result: config.get_users() -> users
+    let call = Regex::new(r"([A-Za-z_][\w]*)\s*(?:::\s*new\s*)?\(").unwrap();
+    let callee = call.captures_iter(&rhs).last().map(|cap| cap[1].to_string())?;
+    if callee == "new" {
+        // Foo::new() was captured as "new"; fall back to the type
+        let owner = Regex::new(r"([A-Za-z_]\w*)\s*::\s*new").unwrap();
+        return Some(split_words(&owner.captures(&rhs)?[1]));
+    }
+    if let Some((_, name)) = KNOWN_CALLS.iter().find(|(call, _)| *call == callee.to_lowercase()) {
+        return Some(vec![name.to_string()]);
+    }
+    if callee.chars().next().is_some_and(|c| c.is_uppercase()) {
+        return Some(split_words(&callee));
+    }
+
+    let mut words = split_words(&callee);
+    if words.len() > 1 && CALL_VERBS.contains(&words[0].as_str()) {
+        words.remove(0);
+    }
+    if words.len() == 1 && CALL_VERBS.contains(&words[0].as_str()) {
+        return None;
+    }
+    Some(words)
+}
+
+// f(x) where `f` is defined in this file as f(name) -> name
+fn from_call_sites(content: &str, var_name: &str, language: &LanguagePattern) -> Option<Vec<String>> {
+    let call = Regex::new(r"\b([A-Za-z_]\w*)\s*\(([^()]*)\)").unwrap();
+    for cap in call.captures_iter(content) {
+        let args: Vec<&str> = cap[2].split(',').map(str::trim).collect();
+        let Some(position) = args.iter().position(|arg| arg.trim_start_matches(['&', '*', '$']).trim_start_matches("mut ") == var_name) else {
+            continue;
+        };
+        let definition = Regex::new(&format!(
+            r"\b(?:def|fn|function|func|fun|sub)\s+{}\s*(?:<[^>]*>)?\s*\(([^)]*)\)",
+            regex::escape(&cap[1])
+        ))
+        .ok()?;
+        let Some(params) = definition.captures(content) else {
+            continue;
+        };
+        let params: Vec<&str> = params[1]
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !matches!(*param, "self" | "&self" | "&mut self" | "mut self" | "this"))
+            .collect();
+        let Some(param) = params.get(position) else {
+            continue;
+        };
+        let name = parameter_name(param, language)?;
+        if name != var_name && bad_variable_rule(&name).is_none() {
+            return Some(split_words(&name));
+        }
+    }
+    None
+}
+
+fn parameter_name(param: &str, language: &LanguagePattern) -> Option<String> {
+    let param = param.split('=').next()?.trim();
+    let name = if let Some((name, _)) = param.split_once(':') {
+        name.trim()
+    } else if *language == LanguagePattern::Go {
+        param.split_whitespace().next()?
+    } else {
+        param.split_whitespace().last()?
+    };
+    let name = name.trim_start_matches(['&', '*', '$']).trim_start_matches("mut ").trim();
+    Some(name.to_string())
+}
+
+fn last_identifier(text: &str) -> Option<String> {
+    // items / self.items / items.iter() / users.values() / get_items()
+    let text = text.trim().trim_end_matches(')').trim_end_matches('(');
+    let ident = Regex::new(r"[A-Za-z_]\w*").unwrap();
+    let idents: Vec<&str> = ident.find_iter(text).map(|m| m.as_str()).collect();
+    idents
+        .iter()
+        .rev()
+        .find(|word| !matches!(**word, "iter" | "iter_mut" | "into_iter" | "enumerate" | "items" | "values" | "keys" | "entries" | "chars" | "lines" | "self" | "this" | "mut") || idents.len() == 1)
+        .or_else(|| idents.first())
+        .map(|word| word.to_string())
+}
+
+fn is_primitive(name: &str) -> bool {
+    PRIMITIVE_TYPES.contains(&name.to_lowercase().as_str())
+}
+
+fn singular(word: &str) -> Option<String> {
+    let single = if let Some(stem) = word.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if word.ends_with("sses") || word.ends_with("xes") || word.ends_with("ches") || word.ends_with("shes") {
+        word[..word.len() - 2].to_string()
+    } else if let Some(stem) = word.strip_suffix('s').filter(|stem| !stem.ends_with('s')) {
+        stem.to_string()
+    } else if word == "children" {
+        "child".to_string()
+    } else {
+        // Not obviously plural: `for c in text` -> `entry` would be a guess, so say nothing
+        return None;
+    };
+    (!single.is_empty()).then_some(single)
+}
+
+fn pluralise(mut words: Vec<String>) -> Option<Vec<String>> {
+    let last = words.pop()?;
+    let plural = if let Some(stem) = last.strip_suffix('y').filter(|stem| !stem.ends_with(['a', 'e', 'i', 'o', 'u'])) {
+        format!("{}ies", stem)
+    } else if last.ends_with('s') || last.ends_with('x') || last.ends_with("ch") || last.ends_with("sh") {
+        format!("{}es", last)
+    } else {
+        format!("{}s", last)
+    };
+    words.push(plural);
+    Some(words)
+}
+
+// UserAccount / user_account / userAccount -> ["user", "account"]
+fn split_words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c == '_' || c == '-' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggested(content: &str, var_name: &str, language: &LanguagePattern) -> Option<String> {
+        let offset = Regex::new(&format!(r"\b{}\b", var_name)).unwrap().find(content).unwrap().start();
+        suggest_name(content, offset, var_name, language)
+    }
+
+    #[test]
+    fn names_come_from_loops_types_and_calls() {
+        assert_eq!(suggested("for x in users:\n    print(x)\n", "x", &LanguagePattern::Python), Some("user".to_string()));
+        assert_eq!(suggested("let v: Vec<Order> = Vec::new();\n", "v", &LanguagePattern::Rust), Some("orders".to_string()));
+        assert_eq!(suggested("c = read_config()\n", "c", &LanguagePattern::Python), Some("config".to_string()));
+        assert_eq!(suggested("let s = get_user_name();\n", "s", &LanguagePattern::JavaScript), Some("userName".to_string()));
+    }
+
+    #[test]
+    fn keywords_are_never_suggested() {
+        assert_eq!(suggested("d = get_class()\n", "d", &LanguagePattern::Python), None);
+        assert_eq!(suggested("let t = get_type();\n", "t", &LanguagePattern::Rust), None);
+        // Not a keyword in Rust
+        assert_eq!(suggested("let d = get_class();\n", "d", &LanguagePattern::Rust), Some("class".to_string()));
+    }
+
+    #[test]
+    fn a_bad_name_moves_on_to_the_next_heuristic() {
+        assert!(bad_variable_rule("item").is_some());
+        let content = "def show(user):\n    print(user)\n\nfor x in items:\n    show(x)\n";
+        assert_eq!(suggested(content, "x", &LanguagePattern::Python), Some("user".to_string()));
+        // With nothing else to go on there's no suggestion, and the theme picks the name
+        assert_eq!(suggested("for x in items:\n    print(x)\n", "x", &LanguagePattern::Python), None);
+    }
+}
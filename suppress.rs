@@ -0,0 +1,135 @@
+use regex::Regex;
+
+use crate::language_patterns::LanguagePattern;
+
+// What a `badvars:` comment asks for
+#[derive(Debug)]
+enum Directive {
+    // `badvars: ignore` - findings declared on the comment's own line
+    Line,
+    // `badvars: ignore-next-line`
+    NextLine,
+    // `badvars: ignore-file`
+    File,
+    // `badvars: allow(x, y)` - these names anywhere in the file
+    Allow(Vec<String>),
+}
+
+struct Suppression {
+    line: usize,
+    text: String,
+    directive: Directive,
+    used: bool,
+}
+
+pub struct Suppressions {
+    entries: Vec<Suppression>,
+}
+
+impl Suppressions {
+    pub fn parse(content: &str, language: &LanguagePattern) -> Self {
+        let prefixes: Vec<String> = language.comment_prefixes().iter().map(|p| regex::escape(p)).collect();
+        let re = Regex::new(&format!(
+            r"(?:{})\s*badvars:\s*(ignore-next-line|ignore-file|ignore|allow\(([^)]*)\))",
+            prefixes.join("|")
+        ))
+        .unwrap();
+
+        let mut entries = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            for cap in re.captures_iter(line) {
+                let directive = match &cap[1] {
+                    "ignore" => Directive::Line,
+                    "ignore-next-line" => Directive::NextLine,
+                    "ignore-file" => Directive::File,
+                    _ => Directive::Allow(
+                        cap[2].split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect(),
+                    ),
+                };
+                entries.push(Suppression {
+                    line: index + 1,
+                    text: cap[0].to_string(),
+                    directive,
+                    used: false,
+                });
+            }
+        }
+        Suppressions { entries }
+    }
+
+    // Whether a declaration of var_name on line is suppressed; remembers which comments did the work
+    pub fn suppresses(&mut self, line: usize, var_name: &str) -> bool {
+        let mut suppressed = false;
+        for entry in &mut self.entries {
+            let applies = match &entry.directive {
+                Directive::Line => entry.line == line,
+                Directive::NextLine => entry.line + 1 == line,
+                Directive::File => true,
+                Directive::Allow(names) => names.iter().any(|name| name == var_name),
+            };
+            if applies {
+                entry.used = true;
+                suppressed = true;
+            }
+        }
+        suppressed
+    }
+
+    // (line, comment) for every suppression that didn't suppress anything
+    pub fn unused(&self) -> Vec<(usize, &str)> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.used)
+            .map(|entry| (entry.line, entry.text.as_str()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_directive_covers_its_lines_and_names() {
+        let content = "\
+x = 1  # badvars: ignore
+# badvars: ignore-next-line
+y = 2
+z = 3
+# badvars: allow(tmp, d)
+";
+        let mut suppressions = Suppressions::parse(content, &LanguagePattern::Python);
+        assert!(suppressions.suppresses(1, "x"));
+        assert!(!suppressions.suppresses(2, "x"));
+        assert!(suppressions.suppresses(3, "y"));
+        assert!(!suppressions.suppresses(4, "z"));
+        assert!(suppressions.suppresses(4, "tmp"));
+        assert!(suppressions.suppresses(1, "d"));
+        assert!(suppressions.unused().is_empty());
+    }
+
+    #[test]
+    fn ignore_file_covers_everything() {
+        let mut suppressions = Suppressions::parse("// badvars: ignore-file\nlet x = 1;\n", &LanguagePattern::Rust);
+        assert!(suppressions.suppresses(2, "x"));
+        assert!(suppressions.suppresses(40, "tmp"));
+    }
+
+    #[test]
+    fn comments_that_suppress_nothing_are_unused() {
+        let content = "count = 1  # badvars: ignore\n# badvars: ignore-next-line\ntmp = 2\n# badvars: allow(d, e)\n";
+        let mut suppressions = Suppressions::parse(content, &LanguagePattern::Python);
+        assert!(suppressions.suppresses(3, "tmp"));
+        assert!(suppressions.suppresses(5, "e"));
+        assert_eq!(suppressions.unused(), [(1, "# badvars: ignore")]);
+
+        let untouched = Suppressions::parse(content, &LanguagePattern::Python);
+        assert_eq!(untouched.unused().len(), 3);
+    }
+
+    #[test]
+    fn only_the_language_comment_prefix_counts() {
+        let suppressions = Suppressions::parse("x = 1  // badvars: ignore\n", &LanguagePattern::Python);
+        assert!(suppressions.unused().is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use std::io;
+use std::path::PathBuf;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use bad_variable_changer::LanguagePattern;
+
+pub struct Rename {
+    pub old: String,
+    pub new: String,
+    pub rule: &'static str,
+    pub selected: bool,
+}
+
+pub struct FileReview {
+    pub path: PathBuf,
+    pub language: LanguagePattern,
+    pub original: String,
+    pub renames: Vec<Rename>,
+}
+
+impl FileReview {
+    pub fn selected(&self) -> Vec<(String, String)> {
+        self.renames
+            .iter()
+            .filter(|r| r.selected)
+            .map(|r| (r.old.clone(), r.new.clone()))
+            .collect()
+    }
+}
+
+// Turns the original text plus the chosen renames into the rewritten text
+pub type Preview = fn(&str, &LanguagePattern, &[(String, String)]) -> String;
+
+// One row of the sidebar: a file header, or one of that file's renames
+#[derive(Clone, Copy, PartialEq)]
+enum Row {
+    File(usize),
+    Rename(usize, usize),
+}
+
+struct Review<'a> {
+    files: &'a mut [FileReview],
+    preview: Preview,
+    languages: Vec<LanguagePattern>,
+    rules: Vec<&'static str>,
+    language_filter: Option<usize>,
+    rule_filter: Option<usize>,
+    list: ListState,
+    scroll: u16,
+}
+
+impl Review<'_> {
+    fn matches(&self, file: &FileReview, rename: &Rename) -> bool {
+        let language_ok = self.language_filter.is_none_or(|i| self.languages[i] == file.language);
+        let rule_ok = self.rule_filter.is_none_or(|i| self.rules[i] == rename.rule);
+        language_ok && rule_ok
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (f, file) in self.files.iter().enumerate() {
+            let visible: Vec<usize> = (0..file.renames.len())
+                .filter(|&r| self.matches(file, &file.renames[r]))
+                .collect();
+            if visible.is_empty() {
+                continue;
+            }
+            rows.push(Row::File(f));
+            rows.extend(visible.into_iter().map(|r| Row::Rename(f, r)));
+        }
+        rows
+    }
+
+    fn current(&self) -> Option<Row> {
+        let rows = self.rows();
+        rows.get(self.list.selected().unwrap_or(0)).copied()
+    }
+
+    fn current_file(&self) -> Option<usize> {
+        match self.current()? {
+            Row::File(f) | Row::Rename(f, _) => Some(f),
+        }
+    }
+
+    fn toggle(&mut self) {
+        match self.current() {
+            Some(Row::Rename(f, r)) => {
+                let rename = &mut self.files[f].renames[r];
+                rename.selected = !rename.selected;
+            }
+            Some(Row::File(f)) => {
+                // Toggling a file flips every visible rename in it together
+                let visible: Vec<usize> = (0..self.files[f].renames.len())
+                    .filter(|&r| self.matches(&self.files[f], &self.files[f].renames[r]))
+                    .collect();
+                let select = visible.iter().any(|&r| !self.files[f].renames[r].selected);
+                for r in visible {
+                    self.files[f].renames[r].selected = select;
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let count = self.rows().len();
+        if count == 0 {
+            return;
+        }
+        let before = self.current_file();
+        let current = self.list.selected().unwrap_or(0) as isize;
+        let next = (current + delta).clamp(0, count as isize - 1) as usize;
+        self.list.select(Some(next));
+        if self.current_file() != before {
+            self.scroll = 0;
+        }
+    }
+
+    fn cycle_language(&mut self) {
+        self.language_filter = cycle(self.language_filter, self.languages.len());
+        self.list.select(Some(0));
+        self.scroll = 0;
+    }
+
+    fn cycle_rule(&mut self) {
+        self.rule_filter = cycle(self.rule_filter, self.rules.len());
+        self.list.select(Some(0));
+        self.scroll = 0;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [sidebar, before, after] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(35),
+            Constraint::Percentage(35),
+        ])
+        .areas(main);
+
+        self.draw_sidebar(frame, sidebar);
+        self.draw_preview(frame, before, after);
+
+        let language = self.language_filter.map_or("all".to_string(), |i| format!("{:?}", self.languages[i]));
+        let rule = self.rule_filter.map_or("all", |i| self.rules[i]);
+        let help = format!(
+            " ↑↓ move  space toggle  l language: {}  f rule: {}  PgUp/PgDn scroll  a apply selected  q quit",
+            language, rule
+        );
+        frame.render_widget(Paragraph::new(help).style(Style::default().add_modifier(Modifier::REVERSED)), status);
+    }
+
+    fn draw_sidebar(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .rows()
+            .into_iter()
+            .map(|row| match row {
+                Row::File(f) => {
+                    let file = &self.files[f];
+                    let chosen = file.renames.iter().filter(|r| r.selected).count();
+                    ListItem::new(Line::from(vec![
+                        Span::styled(file.path.display().to_string(), Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(format!(" ({}/{})", chosen, file.renames.len())),
+                    ]))
+                }
+                Row::Rename(f, r) => {
+                    let rename = &self.files[f].renames[r];
+                    let mark = if rename.selected { "[x]" } else { "[ ]" };
+                    ListItem::new(format!("  {} {} -> {}  ({})", mark, rename.old, rename.new, rename.rule))
+                }
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Renames "))
+            .highlight_style(Style::default().bg(Color::DarkGray));
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_preview(&self, frame: &mut Frame, before_area: Rect, after_area: Rect) {
+        let Some(f) = self.current_file() else {
+            frame.render_widget(Block::default().borders(Borders::ALL).title(" Nothing to review "), before_area);
+            return;
+        };
+        let file = &self.files[f];
+        let rewritten = (self.preview)(&file.original, &file.language, &file.selected());
+
+        // Renames never add or remove lines, so the two sides line up one to one
+        let before_lines: Vec<&str> = file.original.lines().collect();
+        let after_lines: Vec<&str> = rewritten.lines().collect();
+        let changed = Style::default().fg(Color::Yellow);
+
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        for (i, old_line) in before_lines.iter().enumerate() {
+            let new_line = after_lines.get(i).copied().unwrap_or("");
+            let style = if *old_line != new_line { changed } else { Style::default() };
+            before.push(Line::styled(format!("{:>4} {}", i + 1, old_line), style));
+            after.push(Line::styled(format!("{:>4} {}", i + 1, new_line), style));
+        }
+
+        let title = format!(" {} (before) ", file.path.display());
+        frame.render_widget(
+            Paragraph::new(before).block(Block::default().borders(Borders::ALL).title(title)).scroll((self.scroll, 0)),
+            before_area,
+        );
+        frame.render_widget(
+            Paragraph::new(after).block(Block::default().borders(Borders::ALL).title(" after ")).scroll((self.scroll, 0)),
+            after_area,
+        );
+    }
+}
+
+fn cycle(filter: Option<usize>, len: usize) -> Option<usize> {
+    match filter {
+        None if len > 0 => Some(0),
+        Some(i) if i + 1 < len => Some(i + 1),
+        _ => None,
+    }
+}
+
+// Runs the review screen. Returns true when the user asked to apply their selection.
+pub fn review(files: &mut [FileReview], preview: Preview) -> io::Result<bool> {
+    let mut languages: Vec<LanguagePattern> = Vec::new();
+    let mut rules: Vec<&'static str> = Vec::new();
+    for file in files.iter() {
+        if !languages.contains(&file.language) {
+            languages.push(file.language.clone());
+        }
+        for rename in &file.renames {
+            if !rules.contains(&rename.rule) {
+                rules.push(rename.rule);
+            }
+        }
+    }
+
+    let mut state = Review {
+        files,
+        preview,
+        languages,
+        rules,
+        language_filter: None,
+        rule_filter: None,
+        list: ListState::default().with_selected(Some(0)),
+        scroll: 0,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut state);
+    ratatui::restore();
+    result
+}
+
+fn run(terminal: &mut DefaultTerminal, state: &mut Review) -> io::Result<bool> {
+    loop {
+        terminal.draw(|frame| state.draw(frame))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('a') => return Ok(true),
+            KeyCode::Up | KeyCode::Char('k') => state.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => state.move_by(1),
+            KeyCode::Char(' ') => state.toggle(),
+            KeyCode::Char('l') => state.cycle_language(),
+            KeyCode::Char('f') => state.cycle_rule(),
+            KeyCode::PageDown => state.scroll = state.scroll.saturating_add(10),
+            KeyCode::PageUp => state.scroll = state.scroll.saturating_sub(10),
+            _ => {}
+        }
+    }
+}
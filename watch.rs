@@ -0,0 +1,168 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+use bad_variable_changer::{check_rewrite, detect_language, find_source_files, is_hidden, modifyfile, Finding, Rewriter, Scanner};
+
+// Editors either rewrite a file in place or write a temporary and rename it over the original
+const FILE_EVENTS: WatchMask = WatchMask::CLOSE_WRITE.union(WatchMask::MOVED_TO).union(WatchMask::CREATE);
+
+struct Watcher {
+    inotify: Inotify,
+    recursive: bool,
+    // Watched directory for each watch descriptor
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    // Files named on the command line; their directories are watched, but nothing else in them counts
+    files: HashSet<PathBuf>,
+    // Directories whose every source file counts
+    roots: HashSet<PathBuf>,
+    // What was last printed for each file, so unchanged results aren't repeated
+    reported: HashMap<PathBuf, Vec<(String, usize)>>,
+}
+
+// Scans everything under paths once, then rescans just the files that are saved, until interrupted.
+// With a rewriter, saved files are also fixed in place.
+pub fn run(paths: &[PathBuf], recursive: bool, scanner: &Scanner, mut fix: Option<&mut Rewriter>) -> io::Result<()> {
+    let mut watcher = Watcher {
+        inotify: Inotify::init()?,
+        recursive,
+        dirs: HashMap::new(),
+        files: HashSet::new(),
+        roots: HashSet::new(),
+        reported: HashMap::new(),
+    };
+
+    let mut initial = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            watcher.watch_dir(path)?;
+            initial.extend(find_source_files(path, recursive));
+        } else if path.is_file() {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            let wd = watcher.inotify.watches().add(&dir, FILE_EVENTS)?;
+            watcher.dirs.insert(wd, dir.clone());
+            watcher.files.insert(dir.join(path.file_name().unwrap_or_default()));
+            initial.push(dir.join(path.file_name().unwrap_or_default()));
+        } else {
+            eprintln!("Warning: {} does not exist", path.display());
+        }
+    }
+    if watcher.dirs.is_empty() {
+        println!("Nothing to watch.");
+        return Ok(());
+    }
+
+    for file_path in initial {
+        watcher.check(&file_path, scanner, fix.as_deref_mut())?;
+    }
+    println!("Watching for changes (Ctrl-C to stop)...\n");
+
+    let mut buffer = [0; 4096];
+    loop {
+        let mut changed = BTreeSet::new();
+        let mut new_dirs = Vec::new();
+        for event in watcher.inotify.read_events_blocking(&mut buffer)? {
+            let (Some(dir), Some(name)) = (watcher.dirs.get(&event.wd), event.name) else {
+                continue;
+            };
+            let path = dir.join(name);
+            if event.mask.contains(EventMask::ISDIR) {
+                new_dirs.push(path);
+            } else {
+                changed.insert(path);
+            }
+        }
+
+        for dir in new_dirs {
+            if watcher.recursive && watcher.roots.iter().any(|root| dir.starts_with(root)) && !is_hidden(&dir) {
+                // A directory moved or copied in brings its files along without file events of its own
+                watcher.watch_dir(&dir)?;
+                changed.extend(find_source_files(&dir, true));
+            }
+        }
+        for file_path in changed {
+            if watcher.counts(&file_path) {
+                watcher.check(&file_path, scanner, fix.as_deref_mut())?;
+            }
+        }
+    }
+}
+
+impl Watcher {
+    // dir and, when recursive, the directories find_source_files would descend into
+    fn watch_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let wd = self.inotify.watches().add(dir, FILE_EVENTS)?;
+        self.dirs.insert(wd, dir.to_path_buf());
+        self.roots.insert(dir.to_path_buf());
+        if !self.recursive {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if path.is_dir() && !is_hidden(&path) {
+                self.watch_dir(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn counts(&self, file_path: &Path) -> bool {
+        detect_language(file_path).is_some()
+            && file_path.is_file()
+            && (self.files.contains(file_path) || file_path.parent().is_some_and(|dir| self.roots.contains(dir)))
+    }
+
+    fn check(&mut self, file_path: &Path, scanner: &Scanner, fix: Option<&mut Rewriter>) -> io::Result<()> {
+        let Some(language) = detect_language(file_path) else {
+            return Ok(());
+        };
+        // The file may be gone again by the time we get to it
+        let Ok(content) = modifyfile::read_file(file_path.to_str().unwrap()) else {
+            return Ok(());
+        };
+
+        if let Some(rewriter) = fix {
+            let rewrite = rewriter.rewrite(file_path, &content, &language, scanner);
+            crate::print_warnings(&rewrite.warnings);
+            let (rewritten, replacements) = (rewrite.content, rewrite.replacements);
+            if let Err(reason) = check_rewrite(&content, &rewritten, &language) {
+                println!("Not fixing {}: {}\n", file_path.display(), reason);
+            } else if !replacements.is_empty() {
+                modifyfile::write_file(file_path.to_str().unwrap(), &rewritten)?;
+                let renames: Vec<String> = replacements.iter().map(|(old, new)| format!("{} -> {}", old, new)).collect();
+                println!("Fixed {}: {}\n", file_path.display(), renames.join(", "));
+                // Our own write comes back as an event and reports the result
+                return Ok(());
+            }
+        }
+
+        let (findings, warnings) = scanner.scan(file_path, &content, &language);
+        crate::print_warnings(&warnings);
+        let summary: Vec<(String, usize)> = findings.iter().map(|finding| (finding.name.clone(), finding.line)).collect();
+        let previous = self.reported.insert(file_path.to_path_buf(), summary.clone());
+        match previous {
+            Some(previous) if previous == summary => {}
+            // A clean file isn't worth mentioning until it was dirty before
+            None if findings.is_empty() => {}
+            _ => report(file_path, &language, &findings),
+        }
+        Ok(())
+    }
+}
+
+fn report(file_path: &Path, language: &bad_variable_changer::LanguagePattern, findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("No bad variables left in {}\n", file_path.display());
+        return;
+    }
+    println!("Bad variables in {} ({:?}):", file_path.display(), language);
+    for finding in findings {
+        println!("  line {}: {}  ({})", finding.line, finding.name, finding.score);
+    }
+    println!();
+}